@@ -0,0 +1,108 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput,
+};
+use pngme::{Chunk, ChunkType, Png, PngRef};
+
+/// Sizes of the files to benchmark, in MiB of chunk data.
+const SIZES: [usize; 2] = [1, 16];
+
+/// The largest IDAT chunk written by common encoders.
+const IDAT_SIZE: usize = 64 * 1024;
+
+/// A PNG with `mib` MiB of image data split into IDAT chunks, plus a few small chunks.
+/// The data is pseudo-random so that nothing can take shortcuts on it.
+fn large_png(mib: usize) -> Png {
+    let mut state: u32 = 0x2545_f491;
+    let mut data: Vec<u8> = (0..mib * 1024 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+
+    let mut chunks = vec![
+        Chunk::new(ChunkType::IHDR, vec![0, 0, 4, 0, 0, 0, 4, 0, 8, 2, 0, 0, 0]),
+        Chunk::new(ChunkType::tEXt, b"Comment\0benchmark".to_vec()),
+    ];
+    while !data.is_empty() {
+        let rest = data.split_off(data.len().min(IDAT_SIZE));
+        chunks.push(Chunk::new(ChunkType::IDAT, data));
+        data = rest;
+    }
+    chunks.push(Chunk::new(
+        ChunkType::from_str("ruSt").unwrap(),
+        b"hi".to_vec(),
+    ));
+    chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+    Png::from_chunks(chunks)
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for mib in SIZES {
+        let bytes = large_png(mib).as_bytes();
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::new("Png", mib), &bytes, |b, bytes| {
+            b.iter(|| Png::try_from(black_box(bytes.as_slice())).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("PngRef", mib), &bytes, |b, bytes| {
+            b.iter(|| PngRef::try_from(black_box(bytes.as_slice())).unwrap())
+        });
+        group.bench_with_input(
+            BenchmarkId::new("PngRef with CRCs", mib),
+            &bytes,
+            |b, bytes| {
+                b.iter(|| {
+                    let png = PngRef::try_from(black_box(bytes.as_slice())).unwrap();
+                    png.check_crcs().unwrap();
+                    png
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn serialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize");
+    for mib in SIZES {
+        let png = large_png(mib);
+        let length = png.as_bytes().len();
+        group.throughput(Throughput::Bytes(length as u64));
+        group.bench_with_input(BenchmarkId::new("as_bytes", mib), &png, |b, png| {
+            b.iter(|| black_box(png).as_bytes())
+        });
+        group.bench_with_input(BenchmarkId::new("write_to", mib), &png, |b, png| {
+            let mut buffer = Vec::with_capacity(length);
+            b.iter(|| {
+                buffer.clear();
+                black_box(png).write_to(&mut buffer).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+fn crc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crc");
+    for mib in SIZES {
+        let data = large_png(mib).as_bytes();
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(BenchmarkId::new("uncached", mib), &data, |b, data| {
+            b.iter_batched(
+                || Chunk::new(ChunkType::IDAT, data.clone()),
+                |chunk| chunk.crc(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, serialize, crc);
+criterion_main!(benches);
@@ -0,0 +1,580 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::error::{PngError, Result};
+
+/// The animation control chunk (acTL), which marks a PNG as animated. See the APNG spec
+/// https://wiki.mozilla.org/APNG_Specification#.60acTL.60:_The_Animation_Control_Chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationControl {
+    num_frames: u32,
+    num_plays: u32,
+}
+
+impl AnimationControl {
+    /// The length of the acTL chunk data in bytes.
+    pub const LENGTH: usize = 8;
+
+    /// Creates an `AnimationControl`. A `num_plays` of 0 loops forever.
+    pub fn new(num_frames: u32, num_plays: u32) -> Result<AnimationControl> {
+        if num_frames == 0 {
+            return Err(invalid_apng("acTL must declare at least one frame"));
+        }
+        Ok(AnimationControl {
+            num_frames,
+            num_plays,
+        })
+    }
+
+    /// The number of frames in the animation
+    pub fn num_frames(&self) -> u32 {
+        self.num_frames
+    }
+
+    /// The number of times to play the animation, or 0 to loop forever
+    pub fn num_plays(&self) -> u32 {
+        self.num_plays
+    }
+
+    /// Returns an acTL `Chunk` holding this `AnimationControl`.
+    pub fn to_chunk(self) -> Chunk {
+        let mut data = Vec::with_capacity(AnimationControl::LENGTH);
+        data.extend_from_slice(&self.num_frames.to_be_bytes());
+        data.extend_from_slice(&self.num_plays.to_be_bytes());
+        Chunk::new(ChunkType::acTL, data)
+    }
+}
+
+impl TryFrom<&Chunk> for AnimationControl {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = expect_chunk(chunk, ChunkType::acTL, AnimationControl::LENGTH)?;
+        AnimationControl::new(read_u32(data, 0), read_u32(data, 4))
+    }
+}
+
+/// What happens to a frame's region before the next frame is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisposeOp {
+    /// Leave the frame as it is
+    None,
+    /// Clear the region to fully transparent black
+    Background,
+    /// Revert the region to what it was before the frame
+    Previous,
+}
+
+/// How a frame is combined with the output buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendOp {
+    /// Replace the region with the frame
+    Source,
+    /// Alpha blend the frame over the region
+    Over,
+}
+
+impl fmt::Display for DisposeOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisposeOp::None => write!(f, "None"),
+            DisposeOp::Background => write!(f, "Background"),
+            DisposeOp::Previous => write!(f, "Previous"),
+        }
+    }
+}
+
+impl fmt::Display for BlendOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlendOp::Source => write!(f, "Source"),
+            BlendOp::Over => write!(f, "Over"),
+        }
+    }
+}
+
+/// The frame control chunk (fcTL) that describes the region and timing of one frame.
+/// See the APNG spec
+/// https://wiki.mozilla.org/APNG_Specification#.60fcTL.60:_The_Frame_Control_Chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameControl {
+    sequence_number: u32,
+    width: u32,
+    height: u32,
+    x_offset: u32,
+    y_offset: u32,
+    delay_num: u16,
+    delay_den: u16,
+    dispose_op: DisposeOp,
+    blend_op: BlendOp,
+}
+
+impl FrameControl {
+    /// The length of the fcTL chunk data in bytes.
+    pub const LENGTH: usize = 26;
+
+    /// Creates a `FrameControl` for a `width` by `height` region at (`x_offset`,
+    /// `y_offset`), shown for `delay_num / delay_den` seconds.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sequence_number: u32,
+        width: u32,
+        height: u32,
+        x_offset: u32,
+        y_offset: u32,
+        delay_num: u16,
+        delay_den: u16,
+        dispose_op: DisposeOp,
+        blend_op: BlendOp,
+    ) -> Result<FrameControl> {
+        if width == 0 || height == 0 {
+            return Err(invalid_apng("fcTL frame dimensions must be non-zero"));
+        }
+        Ok(FrameControl {
+            sequence_number,
+            width,
+            height,
+            x_offset,
+            y_offset,
+            delay_num,
+            delay_den,
+            dispose_op,
+            blend_op,
+        })
+    }
+
+    /// The position of this chunk among the fcTL and fdAT chunks
+    pub fn sequence_number(&self) -> u32 {
+        self.sequence_number
+    }
+
+    /// The frame width in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The frame height in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The column of the frame's left edge in the full image
+    pub fn x_offset(&self) -> u32 {
+        self.x_offset
+    }
+
+    /// The row of the frame's top edge in the full image
+    pub fn y_offset(&self) -> u32 {
+        self.y_offset
+    }
+
+    /// The numerator of the frame delay
+    pub fn delay_num(&self) -> u16 {
+        self.delay_num
+    }
+
+    /// The denominator of the frame delay, where 0 means 100
+    pub fn delay_den(&self) -> u16 {
+        self.delay_den
+    }
+
+    /// The frame delay in seconds
+    pub fn delay(&self) -> f64 {
+        let den = match self.delay_den {
+            0 => 100,
+            den => den,
+        };
+        self.delay_num as f64 / den as f64
+    }
+
+    /// The `DisposeOp` applied after the frame
+    pub fn dispose_op(&self) -> DisposeOp {
+        self.dispose_op
+    }
+
+    /// The `BlendOp` used to draw the frame
+    pub fn blend_op(&self) -> BlendOp {
+        self.blend_op
+    }
+
+    /// Returns an fcTL `Chunk` holding this `FrameControl`.
+    pub fn to_chunk(self) -> Chunk {
+        let mut data = Vec::with_capacity(FrameControl::LENGTH);
+        for value in [
+            self.sequence_number,
+            self.width,
+            self.height,
+            self.x_offset,
+            self.y_offset,
+        ] {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        data.extend_from_slice(&self.delay_num.to_be_bytes());
+        data.extend_from_slice(&self.delay_den.to_be_bytes());
+        data.push(match self.dispose_op {
+            DisposeOp::None => 0,
+            DisposeOp::Background => 1,
+            DisposeOp::Previous => 2,
+        });
+        data.push(match self.blend_op {
+            BlendOp::Source => 0,
+            BlendOp::Over => 1,
+        });
+        Chunk::new(ChunkType::fcTL, data)
+    }
+}
+
+impl TryFrom<&Chunk> for FrameControl {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = expect_chunk(chunk, ChunkType::fcTL, FrameControl::LENGTH)?;
+        let dispose_op = match data[24] {
+            0 => DisposeOp::None,
+            1 => DisposeOp::Background,
+            2 => DisposeOp::Previous,
+            other => return Err(invalid_apng(&format!("unknown dispose op {}", other))),
+        };
+        let blend_op = match data[25] {
+            0 => BlendOp::Source,
+            1 => BlendOp::Over,
+            other => return Err(invalid_apng(&format!("unknown blend op {}", other))),
+        };
+        FrameControl::new(
+            read_u32(data, 0),
+            read_u32(data, 4),
+            read_u32(data, 8),
+            read_u32(data, 12),
+            read_u32(data, 16),
+            u16::from_be_bytes([data[20], data[21]]),
+            u16::from_be_bytes([data[22], data[23]]),
+            dispose_op,
+            blend_op,
+        )
+    }
+}
+
+/// A frame data chunk (fdAT): part of the compressed image data of a frame after the
+/// first. See the APNG spec
+/// https://wiki.mozilla.org/APNG_Specification#.60fdAT.60:_The_Frame_Data_Chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameData {
+    sequence_number: u32,
+    data: Vec<u8>,
+}
+
+impl FrameData {
+    /// Creates a `FrameData` holding part of a frame's zlib stream.
+    pub fn new(sequence_number: u32, data: Vec<u8>) -> FrameData {
+        FrameData {
+            sequence_number,
+            data,
+        }
+    }
+
+    /// The position of this chunk among the fcTL and fdAT chunks
+    pub fn sequence_number(&self) -> u32 {
+        self.sequence_number
+    }
+
+    /// The compressed frame data, as it would appear in an IDAT chunk
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns an fdAT `Chunk` holding this `FrameData`.
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(4 + self.data.len());
+        data.extend_from_slice(&self.sequence_number.to_be_bytes());
+        data.extend_from_slice(&self.data);
+        Chunk::new(ChunkType::fdAT, data)
+    }
+}
+
+impl TryFrom<&Chunk> for FrameData {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        expect_chunk_type(chunk, ChunkType::fdAT)?;
+        if chunk.data().len() < 4 {
+            return Err(invalid_apng("fdAT is missing its sequence number"));
+        }
+        Ok(FrameData::new(
+            read_u32(chunk.data(), 0),
+            chunk.data()[4..].to_vec(),
+        ))
+    }
+}
+
+/// One frame of an animation: its fcTL and the IDAT or fdAT chunks holding its image.
+#[derive(Debug, Clone)]
+pub struct Frame<'a> {
+    control: FrameControl,
+    chunks: Vec<&'a Chunk>,
+}
+
+impl<'a> Frame<'a> {
+    /// The `FrameControl` describing this frame
+    pub fn control(&self) -> &FrameControl {
+        &self.control
+    }
+
+    /// The IDAT or fdAT chunks holding this frame's image, in file order
+    pub fn chunks(&self) -> &[&'a Chunk] {
+        &self.chunks
+    }
+
+    /// Whether this frame is the static image stored in IDAT
+    pub fn is_default_image(&self) -> bool {
+        self.chunks
+            .first()
+            .is_some_and(|chunk| *chunk.chunk_type() == ChunkType::IDAT)
+    }
+
+    /// The frame's zlib stream, joined from its chunks without fdAT sequence numbers
+    pub fn compressed_data(&self) -> Vec<u8> {
+        self.chunks
+            .iter()
+            .flat_map(|chunk| match *chunk.chunk_type() {
+                ChunkType::fdAT => chunk.data().get(4..).unwrap_or_default(),
+                _ => chunk.data(),
+            })
+            .copied()
+            .collect()
+    }
+}
+
+/// Groups `chunks` into frames. IDAT chunks before the first fcTL are a default image
+/// that is not part of the animation, and are skipped.
+pub fn frames(chunks: &[Chunk]) -> Result<Vec<Frame<'_>>> {
+    let mut frames: Vec<Frame> = Vec::new();
+    for chunk in chunks {
+        match *chunk.chunk_type() {
+            ChunkType::fcTL => frames.push(Frame {
+                control: FrameControl::try_from(chunk)?,
+                chunks: Vec::new(),
+            }),
+            chunk_type @ (ChunkType::IDAT | ChunkType::fdAT) => match frames.last_mut() {
+                Some(frame) => frame.chunks.push(chunk),
+                None if chunk_type == ChunkType::IDAT => (),
+                None => return Err(invalid_apng("fdAT chunk found before any fcTL")),
+            },
+            _ => (),
+        }
+    }
+    Ok(frames)
+}
+
+/// Rewrites the sequence numbers of the fcTL and fdAT chunks so that they count up from 0
+/// in file order. Only chunks whose number is out of place are changed, and chunks too
+/// short to hold a sequence number are left alone. Returns how many were rewritten.
+pub fn renumber_sequence(chunks: &mut [Chunk]) -> usize {
+    let mut sequence_number = 0u32;
+    let mut rewritten = 0;
+    for chunk in chunks.iter_mut() {
+        if matches!(*chunk.chunk_type(), ChunkType::fcTL | ChunkType::fdAT)
+            && chunk.data().len() >= 4
+        {
+            if read_u32(chunk.data(), 0) != sequence_number {
+                chunk.data_mut()[..4].copy_from_slice(&sequence_number.to_be_bytes());
+                rewritten += 1;
+            }
+            sequence_number += 1;
+        }
+    }
+    rewritten
+}
+
+/// Renumbers the sequence like `renumber_sequence`, and sets the frame count in acTL to
+/// match the fcTL chunks. Returns whether any chunk changed.
+pub fn renumber(chunks: &mut [Chunk]) -> bool {
+    let mut changed = renumber_sequence(chunks) > 0;
+    let num_frames = chunks
+        .iter()
+        .filter(|chunk| *chunk.chunk_type() == ChunkType::fcTL)
+        .count() as u32;
+
+    if let Some(actl) = chunks
+        .iter_mut()
+        .find(|chunk| *chunk.chunk_type() == ChunkType::acTL)
+    {
+        if let Ok(control) = AnimationControl::try_from(&*actl) {
+            if num_frames > 0 && control.num_frames() != num_frames {
+                *actl = AnimationControl::new(num_frames, control.num_plays())
+                    .expect("frame count is non-zero")
+                    .to_chunk();
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+fn expect_chunk_type(chunk: &Chunk, chunk_type: ChunkType) -> Result<()> {
+    if *chunk.chunk_type() != chunk_type {
+        return Err(invalid_apng(&format!(
+            "expected a {} chunk, found {}",
+            chunk_type,
+            chunk.chunk_type()
+        )));
+    }
+    Ok(())
+}
+
+fn expect_chunk(chunk: &Chunk, chunk_type: ChunkType, length: usize) -> Result<&[u8]> {
+    expect_chunk_type(chunk, chunk_type)?;
+    if chunk.data().len() != length {
+        return Err(invalid_apng(&format!(
+            "{} must be {} bytes, found {}",
+            chunk_type,
+            length,
+            chunk.data().len()
+        )));
+    }
+    Ok(chunk.data())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().expect("4 bytes"))
+}
+
+fn invalid_apng(reason: &str) -> PngError {
+    PngError::InvalidApng(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn frame_control(sequence_number: u32) -> FrameControl {
+        FrameControl::new(
+            sequence_number,
+            10,
+            8,
+            2,
+            1,
+            1,
+            10,
+            DisposeOp::Background,
+            BlendOp::Over,
+        )
+        .unwrap()
+    }
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn animation() -> Vec<Chunk> {
+        vec![
+            chunk("IHDR", &[0; 13]),
+            AnimationControl::new(2, 0).unwrap().to_chunk(),
+            frame_control(0).to_chunk(),
+            chunk("IDAT", b"first"),
+            frame_control(1).to_chunk(),
+            FrameData::new(2, b"sec".to_vec()).to_chunk(),
+            FrameData::new(3, b"ond".to_vec()).to_chunk(),
+            chunk("IEND", &[]),
+        ]
+    }
+
+    #[test]
+    fn test_animation_control_round_trip() {
+        let control = AnimationControl::new(3, 1).unwrap();
+        let chunk = control.to_chunk();
+        assert_eq!(chunk.length() as usize, AnimationControl::LENGTH);
+        assert_eq!(AnimationControl::try_from(&chunk).unwrap(), control);
+        assert!(AnimationControl::new(0, 0).is_err());
+    }
+
+    #[test]
+    fn test_frame_control_round_trip() {
+        let control = frame_control(7);
+        let chunk = control.to_chunk();
+        assert_eq!(chunk.length() as usize, FrameControl::LENGTH);
+        let parsed = FrameControl::try_from(&chunk).unwrap();
+        assert_eq!(parsed, control);
+        assert_eq!(parsed.delay(), 0.1);
+    }
+
+    #[test]
+    fn test_invalid_frame_control() {
+        let mut data = frame_control(0).to_chunk().data().to_vec();
+        data[24] = 3;
+        assert!(matches!(
+            FrameControl::try_from(&chunk("fcTL", &data)),
+            Err(PngError::InvalidApng(_))
+        ));
+        assert!(FrameControl::try_from(&chunk("fcTL", &data[..20])).is_err());
+        assert!(FrameControl::try_from(&chunk("acTL", &data)).is_err());
+    }
+
+    #[test]
+    fn test_frame_data_round_trip() {
+        let frame_data = FrameData::new(4, b"zlib".to_vec());
+        let parsed = FrameData::try_from(&frame_data.to_chunk()).unwrap();
+        assert_eq!(parsed, frame_data);
+        assert!(FrameData::try_from(&chunk("fdAT", &[0, 0])).is_err());
+    }
+
+    #[test]
+    fn test_frames() {
+        let chunks = animation();
+        let frames = frames(&chunks).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].is_default_image());
+        assert_eq!(frames[0].compressed_data(), b"first");
+        assert!(!frames[1].is_default_image());
+        assert_eq!(frames[1].chunks().len(), 2);
+        assert_eq!(frames[1].compressed_data(), b"second");
+    }
+
+    #[test]
+    fn test_fdat_before_fctl() {
+        let chunks = vec![FrameData::new(0, Vec::new()).to_chunk()];
+        assert!(frames(&chunks).is_err());
+    }
+
+    #[test]
+    fn test_renumber() {
+        let mut chunks = animation();
+        chunks.remove(5);
+        chunks.insert(2, chunk("tEXt", b"a\0b"));
+        assert!(renumber(&mut chunks));
+
+        let sequence_numbers: Vec<u32> = chunks
+            .iter()
+            .filter(|chunk| matches!(*chunk.chunk_type(), ChunkType::fcTL | ChunkType::fdAT))
+            .map(|chunk| read_u32(chunk.data(), 0))
+            .collect();
+        assert_eq!(sequence_numbers, vec![0, 1, 2]);
+        assert_eq!(
+            AnimationControl::try_from(&chunks[1]).unwrap().num_frames(),
+            2
+        );
+
+        chunks.remove(5);
+        assert!(renumber(&mut chunks));
+        assert_eq!(
+            AnimationControl::try_from(&chunks[1]).unwrap().num_frames(),
+            1
+        );
+        assert!(!renumber(&mut chunks));
+    }
+
+    #[test]
+    fn test_renumber_sequence_leaves_actl() {
+        let mut chunks = animation();
+        // Drop the second frame's fcTL, leaving acTL with one frame too many
+        chunks.remove(4);
+        assert_eq!(renumber_sequence(&mut chunks), 2);
+        assert_eq!(
+            AnimationControl::try_from(&chunks[1]).unwrap().num_frames(),
+            2
+        );
+        assert_eq!(renumber_sequence(&mut chunks), 0);
+    }
+}
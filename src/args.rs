@@ -1,11 +1,42 @@
-use clap::{Parser, command};
+use clap::Parser;
 
-use crate::commands::Commands;
+use crate::commands::{Commands, Format};
+use crate::output::{Verbosity, EXIT_CODES_HELP};
 
 #[derive(Debug, Parser)]
 #[command(name = "pngme")]
 #[command(about = "Encode and decode messages into a PNG", long_about = None)]
+#[command(after_help = EXIT_CODES_HELP)]
 pub struct Args {
+    /// Output format for print, decode and validate
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+    /// Only print results and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Also print the files read and written, and the causes of errors
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+    /// Process every file in the FILE_PATH directory and its subdirectories. FILE_PATH
+    /// may also be a quoted glob pattern such as 'photos/*.png'. Files that aren't PNGs
+    /// are skipped
+    #[arg(short, long, global = true)]
+    pub recursive: bool,
+    /// Process this many files at once with --recursive or a glob pattern [default: one
+    /// per CPU]
+    #[arg(short, long, global = true, value_name = "N")]
+    pub jobs: Option<usize>,
     #[command(subcommand)]
-     pub command: Commands
-}
\ No newline at end of file
+    pub command: Commands,
+}
+
+impl Args {
+    /// The `Verbosity` selected by `--quiet` or `--verbose`
+    pub fn verbosity(&self) -> Verbosity {
+        match (self.quiet, self.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, true) => Verbosity::Verbose,
+            (false, false) => Verbosity::Normal,
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::error::Result;
+
+/// How many temporary file names to try before giving up.
+const TEMP_FILE_ATTEMPTS: u32 = 100;
+
+/// Replaces the file at `path` with whatever `write` produces, so that `path` holds either
+/// its old contents or the complete new ones even if writing fails partway.
+///
+/// The new contents go to a temporary file in the same directory, which is synced to disk
+/// and then renamed over `path`. If `path` already exists its permissions and modification
+/// time carry over to the new file, and with a `backup_suffix` the original is kept next
+/// to it under its name plus the suffix. A symlink at `path` is followed, so the file it
+/// points to is replaced rather than the link.
+///
+/// Returns the path of the backup, if one was made.
+pub fn write_atomic<F>(
+    path: &Path,
+    backup_suffix: Option<&OsStr>,
+    write: F,
+) -> Result<Option<PathBuf>>
+where
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    let path = &resolve_symlink(path)?;
+    let original = match fs::metadata(path) {
+        Ok(metadata) => Some(metadata),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+        Err(error) => return Err(error.into()),
+    };
+
+    let temp_file = TempFile::create(path)?;
+    let mut writer = BufWriter::new(&temp_file.file);
+    write(&mut writer)?;
+    writer.into_inner().map_err(|error| error.into_error())?;
+
+    let mut backup = None;
+    if let Some(metadata) = &original {
+        temp_file.file.set_permissions(metadata.permissions())?;
+        temp_file.file.set_modified(metadata.modified()?)?;
+        if let Some(suffix) = backup_suffix {
+            backup = Some(back_up(path, suffix)?);
+        }
+    }
+    temp_file.file.sync_all()?;
+    temp_file.persist(path)?;
+    sync_parent(path)?;
+    Ok(backup)
+}
+
+/// The path that `suffix` backs `path` up to.
+fn backup_path(path: &Path, suffix: &OsStr) -> PathBuf {
+    let mut backup = path.as_os_str().to_os_string();
+    backup.push(suffix);
+    PathBuf::from(backup)
+}
+
+/// A file in the same directory as its target that is deleted on drop unless persisted.
+struct TempFile {
+    path: PathBuf,
+    file: File,
+    persisted: bool,
+}
+
+impl TempFile {
+    fn create(target: &Path) -> io::Result<TempFile> {
+        let name = target
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+        for attempt in 0..TEMP_FILE_ATTEMPTS {
+            let mut temp_name = OsString::from(".");
+            temp_name.push(name);
+            temp_name.push(format!(".{}.{}.tmp", process::id(), attempt));
+            let path = target.with_file_name(temp_name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => {
+                    return Ok(TempFile {
+                        path,
+                        file,
+                        persisted: false,
+                    })
+                }
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(error) => return Err(error),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "could not find an unused temporary file name",
+        ))
+    }
+
+    fn persist(mut self, target: &Path) -> io::Result<()> {
+        fs::rename(&self.path, target)?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn resolve_symlink(path: &Path) -> io::Result<PathBuf> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => fs::canonicalize(path),
+        _ => Ok(path.to_path_buf()),
+    }
+}
+
+/// Keeps the file at `path` under its backup name, replacing any earlier backup.
+fn back_up(path: &Path, suffix: &OsStr) -> io::Result<PathBuf> {
+    let backup = backup_path(path, suffix);
+    match fs::remove_file(&backup) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+        _ => (),
+    }
+    // A hard link keeps the original's metadata without copying it, but isn't supported
+    // everywhere.
+    if fs::hard_link(path, &backup).is_err() {
+        fs::copy(path, &backup)?;
+        let modified = fs::metadata(path)?.modified()?;
+        OpenOptions::new()
+            .write(true)
+            .open(&backup)?
+            .set_modified(modified)?;
+    }
+    Ok(backup)
+}
+
+/// Makes the rename durable by syncing the directory that holds `path`.
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PngError;
+    use std::time::{Duration, SystemTime};
+
+    fn write_bytes(path: &Path, backup: Option<&str>, bytes: &[u8]) -> Result<Option<PathBuf>> {
+        write_atomic(path, backup.map(OsStr::new), |writer| {
+            writer.write_all(bytes)?;
+            Ok(())
+        })
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_creates_new_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.png");
+        assert_eq!(write_bytes(&path, Some(".bak"), b"new").unwrap(), None);
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(file_names(dir.path()), ["new.png"]);
+    }
+
+    #[test]
+    fn test_replaces_file_and_keeps_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, b"old").unwrap();
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+
+        write_bytes(&path, None, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(metadata.modified().unwrap(), modified);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        }
+        assert_eq!(file_names(dir.path()), ["image.png"]);
+    }
+
+    #[test]
+    fn test_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, b"first").unwrap();
+        write_bytes(&path, Some(".bak"), b"second").unwrap();
+        let backup = write_bytes(&path, Some(".bak"), b"third").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"third");
+        assert_eq!(backup, Some(dir.path().join("image.png.bak")));
+        assert_eq!(fs::read(backup.unwrap()).unwrap(), b"second");
+        assert_eq!(file_names(dir.path()), ["image.png", "image.png.bak"]);
+    }
+
+    #[test]
+    fn test_failed_write_keeps_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, b"original").unwrap();
+
+        let result = write_atomic(&path, Some(OsStr::new(".bak")), |writer| {
+            writer.write_all(b"partial")?;
+            Err(PngError::InvalidImageData("failed halfway".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"original");
+        assert_eq!(file_names(dir.path()), ["image.png"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follows_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target.png");
+        let link = dir.path().join("link.png");
+        fs::write(&target, b"old").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_bytes(&link, None, b"new").unwrap();
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read(&target).unwrap(), b"new");
+    }
+}
@@ -0,0 +1,415 @@
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::commands::Format;
+use crate::output::Output;
+use pngme::{Png, PngError, Result};
+
+/// The files that a command runs on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    /// A single file, named on the command line
+    File(OsString),
+    /// Every file matched by a glob pattern or found by `--recursive`, in sorted order
+    Batch(Vec<PathBuf>),
+}
+
+impl Input {
+    /// Expands FILE_PATH into the files to process. A directory is searched if `recursive`
+    /// is set, and a path that doesn't exist but contains `*`, `?` or `[` is matched as a
+    /// glob pattern, which must match at least one file. Any other path is a single file.
+    pub fn resolve(file_path: Option<OsString>, recursive: bool) -> Result<Input> {
+        let file_path =
+            file_path.ok_or_else(|| PngError::InvalidArgument("Invalid filename".to_string()))?;
+        let path = Path::new(&file_path);
+
+        if path.is_dir() {
+            if !recursive {
+                return Err(PngError::InvalidArgument(format!(
+                    "{} is a directory, use --recursive to process the files in it",
+                    path.display()
+                )));
+            }
+            return Ok(Input::Batch(walk(path)?));
+        }
+        if path.exists() || !is_glob(&file_path) {
+            return Ok(Input::File(file_path));
+        }
+
+        let pattern = file_path.to_str().ok_or_else(|| {
+            PngError::InvalidArgument("glob patterns must be valid UTF-8".to_string())
+        })?;
+        let matches = glob::glob(pattern).map_err(|error| {
+            PngError::InvalidArgument(format!("invalid glob pattern: {}", error))
+        })?;
+        let mut files = Vec::new();
+        for entry in matches {
+            let path = entry.map_err(|error| PngError::Io(error.into()))?;
+            if path.is_dir() {
+                if recursive {
+                    files.extend(walk(&path)?);
+                }
+            } else {
+                files.push(path);
+            }
+        }
+        if files.is_empty() {
+            return Err(PngError::InvalidArgument(format!(
+                "no files match {}",
+                pattern
+            )));
+        }
+        files.sort();
+        files.dedup();
+        Ok(Input::Batch(files))
+    }
+
+    /// Whether this is more than one file named on the command line
+    pub fn is_batch(&self) -> bool {
+        matches!(self, Input::Batch(_))
+    }
+}
+
+/// Runs `command` on every file of `input`. A batch is processed on a pool of `jobs`
+/// threads, or one per CPU, skipping files that aren't PNGs. Each file's output is
+/// written after it finishes, followed by a summary, and if any file failed the result is
+/// `PngError::BatchFailed`.
+pub fn run<F>(input: Input, jobs: Option<usize>, out: &Output, command: F) -> Result<()>
+where
+    F: Fn(Option<OsString>, &Output) -> Result<()> + Sync,
+{
+    let files = match input {
+        Input::File(path) => return command(Some(path), out),
+        Input::Batch(files) => files,
+    };
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(jobs.unwrap_or(0))
+        .build()
+        .map_err(|error| PngError::Io(io::Error::other(error)))?;
+    let results: Vec<FileResult> = pool.install(|| {
+        files
+            .par_iter()
+            .map(|path| run_file(path, out, &command))
+            .collect()
+    });
+    report(&files, results, out)
+}
+
+/// What happened to one file of a batch.
+#[derive(Debug)]
+enum Outcome {
+    Succeeded,
+    Skipped,
+    Failed(PngError),
+}
+
+/// The outcome of one file of a batch, with what the command wrote to stdout and stderr.
+#[derive(Debug)]
+struct FileResult {
+    outcome: Outcome,
+    output: Vec<u8>,
+    errors: Vec<u8>,
+}
+
+fn run_file<F>(path: &Path, out: &Output, command: &F) -> FileResult
+where
+    F: Fn(Option<OsString>, &Output) -> Result<()>,
+{
+    let outcome = match is_png(path) {
+        Ok(false) => Outcome::Skipped,
+        Ok(true) => {
+            let file_out = out.buffered();
+            let outcome = match command(Some(path.as_os_str().to_os_string()), &file_out) {
+                Ok(()) => Outcome::Succeeded,
+                Err(error) => Outcome::Failed(error),
+            };
+            let (output, errors) = file_out.into_buffers();
+            return FileResult {
+                outcome,
+                output,
+                errors,
+            };
+        }
+        Err(error) => Outcome::Failed(error.into()),
+    };
+    FileResult {
+        outcome,
+        output: Vec::new(),
+        errors: Vec::new(),
+    }
+}
+
+/// A batch as reported with `--format json`.
+#[derive(Debug, Serialize)]
+struct BatchReport<'a> {
+    files: Vec<FileReport<'a>>,
+    succeeded: usize,
+    failed: usize,
+    skipped: usize,
+}
+
+/// One file of a batch as reported with `--format json`.
+#[derive(Debug, Serialize)]
+struct FileReport<'a> {
+    path: &'a Path,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// The command's JSON output, or its text output if that isn't JSON
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<serde_json::Value>,
+}
+
+fn report(files: &[PathBuf], results: Vec<FileResult>, out: &Output) -> Result<()> {
+    let count = |wanted: fn(&Outcome) -> bool| {
+        results
+            .iter()
+            .filter(|result| wanted(&result.outcome))
+            .count()
+    };
+    let succeeded = count(|outcome| matches!(outcome, Outcome::Succeeded));
+    let failed = count(|outcome| matches!(outcome, Outcome::Failed(_)));
+    let skipped = count(|outcome| matches!(outcome, Outcome::Skipped));
+
+    for result in &results {
+        out.raw_error(&result.errors);
+    }
+    match out.format() {
+        Format::Text => {
+            for (path, result) in files.iter().zip(&results) {
+                if !result.output.is_empty() {
+                    out.result(format_args!("==> {} <==", path.display()))?;
+                    out.raw(&result.output)?;
+                }
+            }
+            for (path, result) in files.iter().zip(&results) {
+                match &result.outcome {
+                    Outcome::Succeeded => out.status(format_args!("{}: ok", path.display()))?,
+                    Outcome::Skipped => {
+                        out.status(format_args!("{}: skipped, not a PNG", path.display()))?
+                    }
+                    Outcome::Failed(error) => out.file_error(path, error),
+                }
+            }
+            out.status(format_args!(
+                "{} {}: {} succeeded, {} failed, {} skipped",
+                files.len(),
+                if files.len() == 1 { "file" } else { "files" },
+                succeeded,
+                failed,
+                skipped
+            ))?;
+        }
+        Format::Json => {
+            let reports = files
+                .iter()
+                .zip(&results)
+                .map(|(path, result)| file_report(path, result))
+                .collect();
+            out.json(&BatchReport {
+                files: reports,
+                succeeded,
+                failed,
+                skipped,
+            })?;
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        failed => Err(PngError::BatchFailed {
+            failed,
+            total: files.len(),
+        }),
+    }
+}
+
+fn file_report<'a>(path: &'a Path, result: &FileResult) -> FileReport<'a> {
+    let (status, error) = match &result.outcome {
+        Outcome::Succeeded => ("succeeded", None),
+        Outcome::Skipped => ("skipped", None),
+        Outcome::Failed(error) => ("failed", Some(error.to_string())),
+    };
+    let output = (!result.output.is_empty()).then(|| {
+        serde_json::from_slice(&result.output).unwrap_or_else(|_| {
+            serde_json::Value::String(String::from_utf8_lossy(&result.output).trim_end().into())
+        })
+    });
+    FileReport {
+        path,
+        status,
+        error,
+        output,
+    }
+}
+
+/// Whether the file at `path` starts with the PNG signature.
+fn is_png(path: &Path) -> io::Result<bool> {
+    let mut header = [0u8; 8];
+    let mut file = File::open(path)?;
+    match file.read_exact(&mut header) {
+        Ok(()) => Ok(header == Png::STANDARD_HEADER),
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+/// Every file below `dir`, in sorted order.
+fn walk(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry.map_err(|error| PngError::Io(error.into()))?;
+        if entry.file_type().is_file() {
+            files.push(entry.into_path());
+        }
+    }
+    Ok(files)
+}
+
+fn is_glob(path: &OsStr) -> bool {
+    path.as_encoded_bytes()
+        .iter()
+        .any(|byte| matches!(byte, b'*' | b'?' | b'['))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Verbosity;
+    use std::fs;
+
+    fn write_files(dir: &Path) {
+        fs::create_dir(dir.join("nested")).unwrap();
+        let png = Png::from_chunks(Vec::new()).as_bytes();
+        fs::write(dir.join("a.png"), &png).unwrap();
+        fs::write(dir.join("nested").join("b.png"), &png).unwrap();
+        fs::write(dir.join("notes.txt"), b"not a png").unwrap();
+    }
+
+    fn batch(input: Input) -> Vec<PathBuf> {
+        match input {
+            Input::Batch(files) => files,
+            Input::File(path) => panic!("expected a batch, got {:?}", path),
+        }
+    }
+
+    #[test]
+    fn test_recursive() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(dir.path());
+        let path = Some(dir.path().as_os_str().to_os_string());
+
+        assert!(matches!(
+            Input::resolve(path.clone(), false),
+            Err(PngError::InvalidArgument(_))
+        ));
+        assert_eq!(
+            batch(Input::resolve(path, true).unwrap()),
+            [
+                dir.path().join("a.png"),
+                dir.path().join("nested").join("b.png"),
+                dir.path().join("notes.txt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_glob() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(dir.path());
+        let pattern = dir.path().join("*.png").into_os_string();
+        assert_eq!(
+            batch(Input::resolve(Some(pattern), false).unwrap()),
+            [dir.path().join("a.png")]
+        );
+
+        let pattern = dir.path().join("*").into_os_string();
+        assert_eq!(batch(Input::resolve(Some(pattern), true).unwrap()).len(), 3);
+
+        let pattern = dir.path().join("*.jpg").into_os_string();
+        assert!(matches!(
+            Input::resolve(Some(pattern), false),
+            Err(PngError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_single_file() {
+        let path = OsString::from("image.png");
+        assert_eq!(
+            Input::resolve(Some(path.clone()), true).unwrap(),
+            Input::File(path)
+        );
+    }
+
+    #[test]
+    fn test_skips_non_png() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(dir.path());
+        assert!(is_png(&dir.path().join("a.png")).unwrap());
+        assert!(!is_png(&dir.path().join("notes.txt")).unwrap());
+    }
+
+    #[test]
+    fn test_run_reports_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(dir.path());
+        let input = Input::resolve(Some(dir.path().as_os_str().to_os_string()), true).unwrap();
+        let out = Output::new(Default::default(), Format::Text).buffered();
+
+        let result = run(input, Some(2), &out, |path, out| {
+            let path = PathBuf::from(path.unwrap());
+            out.result(path.file_name().unwrap().to_string_lossy())?;
+            match path.ends_with("b.png") {
+                true => Err(PngError::ChunkNotFound("ruSt".to_string())),
+                false => Ok(()),
+            }
+        });
+        assert!(matches!(
+            result,
+            Err(PngError::BatchFailed {
+                failed: 1,
+                total: 3
+            })
+        ));
+        let output = String::from_utf8(out.into_buffers().0).unwrap();
+        assert!(output.contains("a.png <==\na.png\n"));
+        assert!(output.contains("notes.txt: skipped, not a PNG"));
+        assert!(output.ends_with("3 files: 1 succeeded, 1 failed, 1 skipped\n"));
+    }
+
+    #[test]
+    fn test_run_keeps_stderr_in_file_order() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(dir.path());
+        let input = Input::resolve(Some(dir.path().as_os_str().to_os_string()), true).unwrap();
+        let out = Output::new(Verbosity::Verbose, Format::Text).buffered();
+
+        let result = run(input, Some(2), &out, |path, out| {
+            let path = PathBuf::from(path.unwrap());
+            for line in 0..100 {
+                out.detail(format_args!("{} {}", path.display(), line));
+            }
+            Ok(())
+        });
+        assert!(result.is_ok());
+        let errors = String::from_utf8(out.into_buffers().1).unwrap();
+        let files = [
+            dir.path().join("a.png"),
+            dir.path().join("nested").join("b.png"),
+        ];
+        let expected: Vec<String> = files
+            .iter()
+            .flat_map(|file| (0..100).map(move |line| format!("{} {}", file.display(), line)))
+            .collect();
+        assert_eq!(errors.lines().collect::<Vec<&str>>(), expected);
+    }
+}
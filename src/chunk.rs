@@ -1,17 +1,23 @@
 use crate::chunk_type::ChunkType;
-use crate::{Error, Result};
-use crc::{Crc, CRC_32_ISO_HDLC};
+use crate::error::{PngError, Result};
+use crc::{Crc, Table, CRC_32_ISO_HDLC};
 use std::convert::TryFrom;
 use std::fmt;
-use std::io::{BufReader, Read};
-use std::str::FromStr;
+use std::io::{ErrorKind, Read};
+use std::sync::OnceLock;
+
+/// The CRC-32 used by PNG. Its slice-by-16 lookup tables are built at compile time.
+static CRC: Crc<u32, Table<16>> = Crc::<u32, Table<16>>::new(&CRC_32_ISO_HDLC);
 
 /// A validated PNG chunk. See the PNG Spec for more details
 /// http://www.libpng.org/pub/png/spec/1.2/PNG-Structure.html
+///
+/// The CRC is computed the first time it's needed and cached until the data changes.
 #[derive(Debug, Clone)]
 pub struct Chunk {
     chunk_type: ChunkType,
     data: Vec<u8>,
+    crc: OnceLock<u32>,
 }
 
 impl Chunk {
@@ -30,27 +36,25 @@ impl Chunk {
         &self.data
     }
 
+    /// The data contained in this chunk, for editing in place
+    pub fn data_mut(&mut self) -> &mut Vec<u8> {
+        // The cached CRC is stale as soon as the data can change
+        self.crc = OnceLock::new();
+        &mut self.data
+    }
+
     /// The CRC of this chunk
     pub fn crc(&self) -> u32 {
-        let crc_algorithm = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-        let chunk_type_bytes = self.chunk_type.bytes();
-        let bytes: Vec<u8> = chunk_type_bytes
-            .to_vec()
-            .iter()
-            .cloned()
-            .chain(self.data.iter().cloned())
-            .collect();
-        crc_algorithm.checksum(&bytes)
+        *self
+            .crc
+            .get_or_init(|| checksum(&self.chunk_type, &self.data))
     }
 
     /// Returns the data stored in this chunk as a `String`. This function will return an error
     /// if the stored data is not valid UTF-8.
     pub fn data_as_string(&self) -> Result<String> {
-        let result = std::str::from_utf8(&self.data);
-        match result {
-            Ok(message) => Ok(message.to_string()),
-            Err(error) => Err(Box::new(error)),
-        }
+        let message = std::str::from_utf8(&self.data)?;
+        Ok(message.to_string())
     }
 
     /// Returns this chunk as a byte sequences described by the PNG spec.
@@ -60,52 +64,119 @@ impl Chunk {
     /// 3. The data itself *(`length` bytes)*
     /// 4. The CRC of the chunk type and data *(4 bytes)*
     pub fn as_bytes(&self) -> Vec<u8> {
-        let length_32: u32 = self.data.len() as u32;
-        let bytes: Vec<u8> = length_32
-            .to_be_bytes()
-            .iter()
-            .cloned()
-            .chain(self.chunk_type.bytes().to_vec().iter().cloned())
-            .chain(self.data.iter().cloned())
-            .chain(self.crc().to_be_bytes().to_vec().iter().cloned())
-            .collect();
+        // Chunk data length + length (4 bytes) + chunk_type (4 bytes) + crc (4 bytes)
+        let mut bytes = Vec::with_capacity(self.data.len() + 12);
+        self.extend_bytes(&mut bytes);
         bytes
     }
 
-    pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
-        Chunk { chunk_type, data }
+    /// Appends the bytes of `as_bytes` to `bytes`.
+    pub(crate) fn extend_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.length().to_be_bytes());
+        bytes.extend_from_slice(&self.chunk_type.bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes.extend_from_slice(&self.crc().to_be_bytes());
     }
-}
 
-impl TryFrom<&[u8]> for Chunk {
-    type Error = Error;
+    pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
+        Chunk {
+            chunk_type,
+            data,
+            crc: OnceLock::new(),
+        }
+    }
 
-    fn try_from(bytes: &[u8]) -> Result<Self> {
-        let mut reader = BufReader::new(bytes);
+    /// Reads a single chunk, including its length prefix, from `reader`. `offset` is the
+    /// position of the chunk within the enclosing stream and is only used for error reporting.
+    pub(crate) fn read_from<R: Read>(reader: &mut R, offset: u64) -> Result<Chunk> {
         let mut buffer: [u8; 4] = [0, 0, 0, 0];
+        reader
+            .read_exact(&mut buffer)
+            .map_err(|error| truncated_at(error, offset))?;
+        Chunk::read_after_length(reader, u32::from_be_bytes(buffer), offset)
+    }
 
-        reader.read_exact(&mut buffer)?;
-        let data_length = u32::from_be_bytes(buffer);
+    /// Reads the type, data and CRC of a chunk whose length prefix has already been consumed,
+    /// and verifies the CRC.
+    pub(crate) fn read_after_length<R: Read>(
+        reader: &mut R,
+        data_length: u32,
+        offset: u64,
+    ) -> Result<Chunk> {
+        let (chunk, crc) = Chunk::read_unchecked_after_length(reader, data_length, offset)?;
+        chunk.check_crc(crc, offset)?;
+        Ok(chunk)
+    }
 
-        reader.read_exact(&mut buffer)?;
-        let chunk_type_data = String::from_utf8(buffer.to_vec()).unwrap();
+    /// Like `read_after_length`, but returns the stored CRC instead of verifying it.
+    pub(crate) fn read_unchecked_after_length<R: Read>(
+        reader: &mut R,
+        data_length: u32,
+        offset: u64,
+    ) -> Result<(Chunk, u32)> {
+        let mut buffer: [u8; 4] = [0, 0, 0, 0];
 
-        let mut data_buffer = vec![0u8; data_length as usize];
-        reader.read_exact(&mut data_buffer)?;
+        reader
+            .read_exact(&mut buffer)
+            .map_err(|error| truncated_at(error, offset))?;
+        let chunk_type = ChunkType::try_from(buffer)?;
+
+        // Read through `take` so a corrupt length can't make us allocate more than the
+        // stream actually holds.
+        let mut data_buffer = Vec::new();
+        reader
+            .by_ref()
+            .take(data_length as u64)
+            .read_to_end(&mut data_buffer)?;
+        if data_buffer.len() < data_length as usize {
+            return Err(PngError::TruncatedChunk { offset });
+        }
 
-        reader.read_exact(&mut buffer)?;
+        reader
+            .read_exact(&mut buffer)
+            .map_err(|error| truncated_at(error, offset))?;
         let crc = u32::from_be_bytes(buffer);
 
-        let chunk_type = ChunkType::from_str(&chunk_type_data).unwrap();
-        let chunk = Chunk::new(chunk_type, data_buffer);
+        Ok((Chunk::new(chunk_type, data_buffer), crc))
+    }
 
-        if crc != chunk.crc() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "CRC Checksum does not match",
-            )));
+    /// Returns `PngError::CrcMismatch` if `stored`, read from the chunk at `offset`, is not
+    /// the CRC of this chunk.
+    pub(crate) fn check_crc(&self, stored: u32, offset: u64) -> Result<()> {
+        let actual = self.crc();
+        if stored != actual {
+            return Err(PngError::CrcMismatch {
+                chunk_type: self.chunk_type,
+                offset,
+                expected: stored,
+                actual,
+            });
         }
-        Ok(chunk)
+        Ok(())
+    }
+}
+
+/// The CRC of a chunk with `chunk_type` and `data`, computed without copying either.
+pub(crate) fn checksum(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+    let mut digest = CRC.digest();
+    digest.update(&chunk_type.bytes());
+    digest.update(data);
+    digest.finalize()
+}
+
+/// Maps an unexpected EOF into a `TruncatedChunk` error for the chunk at `offset`.
+fn truncated_at(error: std::io::Error, offset: u64) -> PngError {
+    match error.kind() {
+        ErrorKind::UnexpectedEof => PngError::TruncatedChunk { offset },
+        _ => PngError::Io(error),
+    }
+}
+
+impl TryFrom<&[u8]> for Chunk {
+    type Error = PngError;
+
+    fn try_from(mut bytes: &[u8]) -> Result<Self> {
+        Chunk::read_from(&mut bytes, 0)
     }
 }
 
@@ -182,6 +253,16 @@ mod tests {
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_crc_updates_with_data() {
+        let mut chunk = testing_chunk();
+        assert_eq!(chunk.crc(), 2882656334);
+        chunk.data_mut().push(b'!');
+        let expected = Chunk::new(*chunk.chunk_type(), chunk.data().to_vec()).crc();
+        assert_ne!(chunk.crc(), 2882656334);
+        assert_eq!(chunk.crc(), expected);
+    }
+
     #[test]
     fn test_valid_chunk_from_bytes() {
         let data_length: u32 = 42;
@@ -230,6 +311,69 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_crc_mismatch_error() {
+        let data_length: u32 = 42;
+        let chunk_type = "RuSt".as_bytes();
+        let message_bytes = "This is where your secret message will be!".as_bytes();
+        let crc: u32 = 2882656333;
+
+        let chunk_data: Vec<u8> = data_length
+            .to_be_bytes()
+            .iter()
+            .chain(chunk_type.iter())
+            .chain(message_bytes.iter())
+            .chain(crc.to_be_bytes().iter())
+            .copied()
+            .collect();
+
+        match Chunk::try_from(chunk_data.as_ref()) {
+            Err(PngError::CrcMismatch {
+                chunk_type,
+                offset,
+                expected,
+                actual,
+            }) => {
+                assert_eq!(chunk_type.to_string(), "RuSt");
+                assert_eq!(offset, 0);
+                assert_eq!(expected, 2882656333);
+                assert_eq!(actual, 2882656334);
+            }
+            other => panic!("expected CrcMismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_truncated_chunk_error() {
+        let data_length: u32 = 42;
+        let chunk_data: Vec<u8> = data_length
+            .to_be_bytes()
+            .iter()
+            .chain("RuSt".as_bytes().iter())
+            .chain("too short".as_bytes().iter())
+            .copied()
+            .collect();
+
+        let chunk = Chunk::try_from(chunk_data.as_ref());
+
+        assert!(matches!(chunk, Err(PngError::TruncatedChunk { offset: 0 })));
+    }
+
+    #[test]
+    fn test_invalid_chunk_type_error() {
+        let chunk_data: Vec<u8> = 0u32
+            .to_be_bytes()
+            .iter()
+            .chain([0xff, 0xfe, 0x20, 0x31].iter())
+            .chain(0u32.to_be_bytes().iter())
+            .copied()
+            .collect();
+
+        let chunk = Chunk::try_from(chunk_data.as_ref());
+
+        assert!(matches!(chunk, Err(PngError::InvalidChunkType(_))));
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -0,0 +1,183 @@
+use std::convert::TryFrom;
+
+use crate::chunk::{self, Chunk};
+use crate::chunk_type::ChunkType;
+use crate::error::{PngError, Result};
+
+/// A PNG chunk borrowed from the buffer it was parsed from.
+///
+/// Unlike `Chunk`, parsing a `ChunkRef` copies nothing and doesn't check the CRC, so
+/// scanning the structure of a file is cheap. Call `check_crc` to verify the stored CRC,
+/// and `to_chunk` for an owned copy that can be edited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkRef<'a> {
+    chunk_type: ChunkType,
+    data: &'a [u8],
+    stored_crc: u32,
+    offset: u64,
+}
+
+impl<'a> ChunkRef<'a> {
+    /// The number of bytes a chunk takes up besides its data: the length, type and CRC.
+    pub const OVERHEAD: usize = 12;
+
+    /// Parses the chunk at the start of `bytes`, returning it along with the rest of the
+    /// buffer. `offset` is the position of the chunk within the enclosing file and is used
+    /// for error reporting.
+    pub fn parse(bytes: &'a [u8], offset: u64) -> Result<(ChunkRef<'a>, &'a [u8])> {
+        if bytes.len() < ChunkRef::OVERHEAD {
+            return Err(PngError::TruncatedChunk { offset });
+        }
+        let (length, rest) = bytes.split_at(4);
+        let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize;
+        let (chunk_type, rest) = rest.split_at(4);
+        let chunk_type =
+            ChunkType::try_from([chunk_type[0], chunk_type[1], chunk_type[2], chunk_type[3]])?;
+        // Compare against what's left rather than adding to `length`, which could overflow.
+        if rest.len() - 4 < length {
+            return Err(PngError::TruncatedChunk { offset });
+        }
+        let (data, rest) = rest.split_at(length);
+        let (crc, rest) = rest.split_at(4);
+
+        let chunk = ChunkRef {
+            chunk_type,
+            data,
+            stored_crc: u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]),
+            offset,
+        };
+        Ok((chunk, rest))
+    }
+
+    /// The length of the data portion of this chunk.
+    pub fn length(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    /// The `ChunkType` of this chunk
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
+
+    /// The raw data contained in this chunk, borrowed from the parsed buffer
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The byte offset of this chunk's length field in the parsed file
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The CRC stored in the file, which may not match the chunk's contents
+    pub fn stored_crc(&self) -> u32 {
+        self.stored_crc
+    }
+
+    /// The CRC computed from the chunk type and data
+    pub fn crc(&self) -> u32 {
+        chunk::checksum(&self.chunk_type, self.data)
+    }
+
+    /// Returns `PngError::CrcMismatch` if the stored CRC doesn't match the computed one.
+    pub fn check_crc(&self) -> Result<()> {
+        let actual = self.crc();
+        if self.stored_crc != actual {
+            return Err(PngError::CrcMismatch {
+                chunk_type: self.chunk_type,
+                offset: self.offset,
+                expected: self.stored_crc,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Copies this chunk into an owned `Chunk`. The CRC is not checked.
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(self.chunk_type, self.data.to_vec())
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for ChunkRef<'a> {
+    type Error = PngError;
+
+    /// Parses a single chunk that starts at the beginning of `bytes`. Any bytes after the
+    /// chunk are ignored.
+    fn try_from(bytes: &'a [u8]) -> Result<Self> {
+        Ok(ChunkRef::parse(bytes, 0)?.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk_bytes(crc: u32) -> Vec<u8> {
+        let message_bytes = "This is where your secret message will be!".as_bytes();
+        (message_bytes.len() as u32)
+            .to_be_bytes()
+            .iter()
+            .chain("RuSt".as_bytes())
+            .chain(message_bytes)
+            .chain(crc.to_be_bytes().iter())
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn test_borrows_data() {
+        let bytes = chunk_bytes(2882656334);
+        let chunk = ChunkRef::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(chunk.length(), 42);
+        assert_eq!(chunk.chunk_type().to_string(), "RuSt");
+        assert_eq!(chunk.data().as_ptr(), bytes[8..].as_ptr());
+        assert_eq!(chunk.stored_crc(), 2882656334);
+        assert_eq!(chunk.crc(), 2882656334);
+        assert!(chunk.check_crc().is_ok());
+    }
+
+    #[test]
+    fn test_crc_checked_lazily() {
+        let bytes = chunk_bytes(2882656333);
+        let chunk = ChunkRef::try_from(bytes.as_slice()).unwrap();
+        match chunk.check_crc() {
+            Err(PngError::CrcMismatch {
+                expected, actual, ..
+            }) => {
+                assert_eq!(expected, 2882656333);
+                assert_eq!(actual, 2882656334);
+            }
+            other => panic!("expected CrcMismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_returns_rest() {
+        let mut bytes = chunk_bytes(2882656334);
+        bytes.extend_from_slice(&[1, 2, 3]);
+        let (chunk, rest) = ChunkRef::parse(&bytes, 33).unwrap();
+        assert_eq!(chunk.offset(), 33);
+        assert_eq!(rest, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = chunk_bytes(2882656334);
+        for length in [0, 11, bytes.len() - 1] {
+            assert!(matches!(
+                ChunkRef::parse(&bytes[..length], 8),
+                Err(PngError::TruncatedChunk { offset: 8 })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_to_chunk() {
+        let bytes = chunk_bytes(2882656334);
+        let chunk = ChunkRef::try_from(bytes.as_slice()).unwrap().to_chunk();
+        assert_eq!(*chunk.chunk_type(), ChunkType::from_str("RuSt").unwrap());
+        assert_eq!(chunk.as_bytes(), bytes);
+    }
+}
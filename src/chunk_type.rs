@@ -1,96 +1,122 @@
 use std::{fmt::Display, str::FromStr};
 
-#[derive(Debug, Clone)]
-pub struct ChunkType {
-    data: String,
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
+
+use crate::error::PngError;
+
+/// A four byte chunk type code. Every byte is an ASCII letter, and the case of each
+/// letter encodes one of the chunk's property bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChunkType([u8; 4]);
+
+#[allow(non_upper_case_globals)]
+impl ChunkType {
+    pub const IHDR: ChunkType = ChunkType::known(*b"IHDR");
+    pub const PLTE: ChunkType = ChunkType::known(*b"PLTE");
+    pub const IDAT: ChunkType = ChunkType::known(*b"IDAT");
+    pub const IEND: ChunkType = ChunkType::known(*b"IEND");
+    pub const cHRM: ChunkType = ChunkType::known(*b"cHRM");
+    pub const gAMA: ChunkType = ChunkType::known(*b"gAMA");
+    pub const iCCP: ChunkType = ChunkType::known(*b"iCCP");
+    pub const sBIT: ChunkType = ChunkType::known(*b"sBIT");
+    pub const sRGB: ChunkType = ChunkType::known(*b"sRGB");
+    pub const bKGD: ChunkType = ChunkType::known(*b"bKGD");
+    pub const hIST: ChunkType = ChunkType::known(*b"hIST");
+    pub const tRNS: ChunkType = ChunkType::known(*b"tRNS");
+    pub const pHYs: ChunkType = ChunkType::known(*b"pHYs");
+    pub const sPLT: ChunkType = ChunkType::known(*b"sPLT");
+    pub const tIME: ChunkType = ChunkType::known(*b"tIME");
+    pub const tEXt: ChunkType = ChunkType::known(*b"tEXt");
+    pub const zTXt: ChunkType = ChunkType::known(*b"zTXt");
+    pub const iTXt: ChunkType = ChunkType::known(*b"iTXt");
+    pub const acTL: ChunkType = ChunkType::known(*b"acTL");
+    pub const fcTL: ChunkType = ChunkType::known(*b"fcTL");
+    pub const fdAT: ChunkType = ChunkType::known(*b"fdAT");
+
+    /// Builds one of the constants above, failing to compile if `bytes` isn't valid.
+    const fn known(bytes: [u8; 4]) -> ChunkType {
+        assert!(
+            all_ascii_letters(&bytes),
+            "chunk types must be ASCII letters"
+        );
+        ChunkType(bytes)
+    }
+}
+
+const fn all_ascii_letters(bytes: &[u8; 4]) -> bool {
+    let mut i = 0;
+    while i < bytes.len() {
+        if !bytes[i].is_ascii_alphabetic() {
+            return false;
+        }
+        i += 1;
+    }
+    true
 }
 
 impl TryFrom<[u8; 4]> for ChunkType {
-    type Error = &'static str;
-
-   fn try_from(value: [u8; 4]) -> Result<Self, Self::Error> {
-        let string_result = std::str::from_utf8(&value);
-        match string_result {
-            Ok(result) => Ok(ChunkType {
-                data: result.to_string(),
-            }),
-            Err(_) => Err("Error converting from provided bytes"),
+    type Error = PngError;
+
+    fn try_from(value: [u8; 4]) -> Result<Self, Self::Error> {
+        if !all_ascii_letters(&value) {
+            return Err(PngError::InvalidChunkType(value.to_vec()));
         }
+        Ok(ChunkType(value))
     }
 }
 
 impl FromStr for ChunkType {
-    type Err = &'static str;
+    type Err = PngError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() != 4 {
-            return Err("string is invalid. Not 4 characters");
-        }
-        if s.chars().all(char::is_alphabetic) {
-            return Ok(ChunkType {
-                data: s.to_string(),
-            });
-        } else {
-            return Err("Non alphabetic string");
-        }
+        let bytes = <[u8; 4]>::try_from(s.as_bytes())
+            .map_err(|_| PngError::InvalidChunkType(s.as_bytes().to_vec()))?;
+        ChunkType::try_from(bytes)
     }
 }
 
 impl Display for ChunkType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.data)
+        write!(f, "{}", self.as_str())
     }
 }
 
-impl PartialEq for ChunkType {
-    fn eq(&self, other: &Self) -> bool {
-        self.data == other.data
+#[cfg(feature = "serde")]
+impl Serialize for ChunkType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
     }
 }
 
-impl Eq for ChunkType {
-    fn assert_receiver_is_total_eq(&self) {}
-}
-
 impl ChunkType {
     pub fn bytes(&self) -> [u8; 4] {
-        let mut array = [0u8; 4];
-        for (&x, p) in self.data.as_bytes().iter().zip(array.iter_mut()) {
-            *p = x;
-        }
-        array
+        self.0
     }
 
-    pub fn is_valid(&self) -> bool {
-        let is_alphabetic = self.data.chars().all(char::is_alphabetic);
-        let bytes = self.data.as_bytes();
-        let is_reserved = u8::is_ascii_uppercase(bytes.get(2).unwrap());
+    /// The chunk type as a four letter string
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.0).expect("chunk types are ASCII")
+    }
 
-        is_alphabetic && is_reserved
+    pub fn is_valid(&self) -> bool {
+        self.is_reserved_bit_valid()
     }
 
     pub fn is_critical(&self) -> bool {
-        let bytes = self.data.as_bytes();
-
-        u8::is_ascii_uppercase(bytes.get(0).unwrap())
+        self.0[0].is_ascii_uppercase()
     }
 
     pub fn is_public(&self) -> bool {
-        let bytes = self.data.as_bytes();
-
-        u8::is_ascii_uppercase(bytes.get(1).unwrap())
+        self.0[1].is_ascii_uppercase()
     }
 
     pub fn is_reserved_bit_valid(&self) -> bool {
-        let bytes = self.data.as_bytes();
-
-        u8::is_ascii_uppercase(bytes.get(2).unwrap())
+        self.0[2].is_ascii_uppercase()
     }
 
     pub fn is_safe_to_copy(&self) -> bool {
-        let bytes = self.data.as_bytes();
-
-        u8::is_ascii_lowercase(bytes.get(3).unwrap())
+        self.0[3].is_ascii_lowercase()
     }
 }
 
@@ -184,6 +210,25 @@ mod tests {
         assert_eq!(&chunk.to_string(), "RuSt");
     }
 
+    #[test]
+    pub fn test_non_letters_are_rejected() {
+        assert!(ChunkType::try_from(*b"Ru1t").is_err());
+        assert!(ChunkType::try_from(*b"Ru t").is_err());
+        assert!(ChunkType::try_from([0xc3, 0x84, b'B', b'C']).is_err());
+        assert!(ChunkType::from_str("\u{c4}BCD").is_err());
+        assert!(ChunkType::from_str("\u{c4}BC").is_err());
+        assert!(ChunkType::from_str("RuStX").is_err());
+    }
+
+    #[test]
+    pub fn test_known_chunk_types() {
+        assert_eq!(ChunkType::IHDR, ChunkType::from_str("IHDR").unwrap());
+        assert_eq!(ChunkType::tEXt.as_str(), "tEXt");
+        assert!(ChunkType::IEND.is_critical());
+        assert!(!ChunkType::gAMA.is_critical());
+        assert!(ChunkType::IDAT < ChunkType::IEND);
+    }
+
     #[test]
     pub fn test_chunk_type_trait_impls() {
         let chunk_type_1: ChunkType = TryFrom::try_from([82, 117, 83, 116]).unwrap();
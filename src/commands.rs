@@ -1,33 +1,172 @@
 use std::ffi::OsString;
 
-use clap::{Subcommand};
+use clap::{Args, Subcommand, ValueEnum};
+
+use pngme::fragment::MAX_CHUNK_LENGTH;
+use pngme::ChunkPosition;
 
 #[derive(Debug, Subcommand)]
 pub enum Commands {
-    Encode{
+    /// Hide a message in a PNG
+    Encode {
         #[arg(required(true))]
         file_path: Option<OsString>,
         #[arg(required(true))]
         chunk_type: String,
-        #[arg(required(true))]
-        message: String,
+        #[arg(required_unless_present("input_file"), conflicts_with("input_file"))]
+        message: Option<String>,
         #[arg(required(false))]
-        output_file: Option<OsString>
+        output_file: Option<OsString>,
+        /// Write the resulting PNG here instead of OUTPUT_FILE or FILE_PATH
+        #[arg(short, long, conflicts_with("output_file"))]
+        output: Option<OsString>,
+        /// Hide the contents of this file instead of MESSAGE, or "-" to read stdin
+        #[arg(long)]
+        input_file: Option<OsString>,
+        /// Where to place the message chunk in the file
+        #[arg(long, value_enum, default_value_t = Position::BeforeIend)]
+        position: Position,
+        /// Keyword to store the message under when CHUNK_TYPE is tEXt, zTXt or iTXt
+        #[arg(long)]
+        keyword: Option<String>,
+        #[command(flatten)]
+        secret: SecretArgs,
+        /// Split the message across several chunks of at most this many bytes
+        #[arg(long, default_value_t = MAX_CHUNK_LENGTH)]
+        max_chunk_size: usize,
+        /// Where to hide the message. CHUNK_TYPE is ignored for lsb
+        #[arg(long, value_enum, default_value_t = Method::Chunk)]
+        method: Method,
+        #[command(flatten)]
+        backup: BackupArgs,
     },
+    /// Recover a message hidden in a PNG
     Decode {
         #[arg(required(true))]
         file_path: Option<OsString>,
         #[arg(required(true))]
         chunk_type: String,
+        /// Keyword to read the message from when CHUNK_TYPE is tEXt, zTXt or iTXt
+        #[arg(long)]
+        keyword: Option<String>,
+        #[command(flatten)]
+        secret: SecretArgs,
+        /// Write the raw message bytes to this file, or "-" for stdout
+        #[arg(long)]
+        output_file: Option<OsString>,
+        /// Where the message is hidden. CHUNK_TYPE is ignored for lsb
+        #[arg(long, value_enum, default_value_t = Method::Chunk)]
+        method: Method,
     },
+    /// Remove a message from a PNG
     Remove {
         #[arg(required(true))]
         file_path: Option<OsString>,
         #[arg(required(true))]
         chunk_type: String,
+        /// Keyword of the text to remove when CHUNK_TYPE is tEXt, zTXt or iTXt
+        #[arg(long)]
+        keyword: Option<String>,
+        #[command(flatten)]
+        backup: BackupArgs,
     },
+    /// List the chunks of a PNG
     Print {
         #[arg(required(true))]
         file_path: Option<OsString>,
+        /// Dump the data of every chunk as hex
+        #[arg(long)]
+        hex: bool,
+    },
+    /// Check the file against the PNG chunk ordering rules
+    Validate {
+        #[arg(required(true))]
+        file_path: Option<OsString>,
+    },
+    /// Recompute bad CRCs, drop a truncated final chunk and add a missing IEND
+    Repair {
+        #[arg(required(true))]
+        file_path: Option<OsString>,
+        /// Write the repaired PNG here instead of FILE_PATH
+        #[arg(short, long)]
+        output: Option<OsString>,
+        #[command(flatten)]
+        backup: BackupArgs,
+    },
+    /// List the frames of an animated PNG
+    Frames {
+        #[arg(required(true))]
+        file_path: Option<OsString>,
+    },
+}
+
+// Options for keeping the file that a command overwrites, see `SecretArgs` for why this
+// isn't a doc comment.
+#[derive(Debug, Args)]
+pub struct BackupArgs {
+    /// Keep the overwritten file under its name plus SUFFIX
+    #[arg(
+        long,
+        value_name = "SUFFIX",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = DEFAULT_BACKUP_SUFFIX
+    )]
+    pub backup: Option<OsString>,
+}
+
+/// The backup suffix used when `--backup` is given without one.
+pub const DEFAULT_BACKUP_SUFFIX: &str = ".bak";
+
+// Options for encrypting or decrypting the message. A `//` comment, as clap would show a
+// doc comment as the description of every subcommand that flattens these options.
+#[derive(Debug, Args)]
+pub struct SecretArgs {
+    /// Encrypt or decrypt the message with a key derived from this password
+    #[arg(long, conflicts_with = "key_file")]
+    pub password: Option<String>,
+    /// Encrypt or decrypt the message with a key derived from this file's contents
+    #[arg(long)]
+    pub key_file: Option<OsString>,
+}
+
+/// Ways of hiding a message in a PNG.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Method {
+    /// In a chunk of its own, which is quick but easily stripped
+    Chunk,
+    /// In the least significant bits of the pixel samples, which survives chunk
+    /// stripping but not lossy re-encoding
+    Lsb,
+}
+
+/// How `print`, `decode` and `validate` write their results to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Format {
+    /// Human readable text
+    #[default]
+    Text,
+    /// A single JSON document
+    Json,
+}
+
+/// Chunk placements selectable from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Position {
+    /// Immediately before IEND
+    BeforeIend,
+    /// Immediately after IHDR
+    AfterIhdr,
+    /// Immediately before the first IDAT
+    BeforeIdat,
+}
+
+impl From<Position> for ChunkPosition {
+    fn from(position: Position) -> Self {
+        match position {
+            Position::BeforeIend => ChunkPosition::BeforeIend,
+            Position::AfterIhdr => ChunkPosition::AfterIhdr,
+            Position::BeforeIdat => ChunkPosition::BeforeFirstIdat,
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,123 @@
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+use crate::error::{PngError, Result};
+
+/// Version 1: Argon2id with default parameters, ChaCha20-Poly1305.
+const VERSION: u8 = 1;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const HEADER_LENGTH: usize = 1 + SALT_LENGTH + NONCE_LENGTH;
+
+/// Encrypts `plaintext` with a key derived from `secret`, returning a versioned envelope:
+///
+/// 1. Envelope version *(1 byte)*
+/// 2. Argon2id salt *(16 bytes)*
+/// 3. ChaCha20-Poly1305 nonce *(12 bytes)*
+/// 4. Ciphertext followed by the authentication tag
+///
+/// `secret` is either a password or the contents of a key file; both go through the KDF.
+pub fn encrypt(plaintext: &[u8], secret: &[u8]) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(secret, &salt)?);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| PngError::InvalidEnvelope("encryption failed".to_string()))?;
+
+    let mut envelope = Vec::with_capacity(HEADER_LENGTH + ciphertext.len());
+    envelope.push(VERSION);
+    envelope.extend_from_slice(&salt);
+    envelope.extend_from_slice(&nonce);
+    envelope.extend(ciphertext);
+    Ok(envelope)
+}
+
+/// Decrypts an envelope produced by `encrypt`. Returns `PngError::AuthenticationFailed`
+/// if `secret` is wrong or the envelope has been tampered with.
+pub fn decrypt(envelope: &[u8], secret: &[u8]) -> Result<Vec<u8>> {
+    if envelope.len() < HEADER_LENGTH {
+        return Err(PngError::InvalidEnvelope(
+            "envelope is truncated".to_string(),
+        ));
+    }
+    let version = envelope[0];
+    if version != VERSION {
+        return Err(PngError::InvalidEnvelope(format!(
+            "unsupported envelope version {}",
+            version
+        )));
+    }
+
+    let salt = &envelope[1..1 + SALT_LENGTH];
+    let nonce = Nonce::from_slice(&envelope[HEADER_LENGTH - NONCE_LENGTH..HEADER_LENGTH]);
+    let cipher = ChaCha20Poly1305::new(&derive_key(secret, salt)?);
+    cipher
+        .decrypt(nonce, &envelope[HEADER_LENGTH..])
+        .map_err(|_| PngError::AuthenticationFailed)
+}
+
+fn derive_key(secret: &[u8], salt: &[u8]) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(secret, salt, &mut key)
+        .map_err(|error| PngError::InvalidEnvelope(format!("key derivation failed: {}", error)))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let envelope = encrypt(b"attack at dawn", b"hunter2").unwrap();
+        assert!(!envelope
+            .windows(b"attack".len())
+            .any(|window| window == b"attack"));
+        assert_eq!(decrypt(&envelope, b"hunter2").unwrap(), b"attack at dawn");
+    }
+
+    #[test]
+    fn test_wrong_secret() {
+        let envelope = encrypt(b"attack at dawn", b"hunter2").unwrap();
+        assert!(matches!(
+            decrypt(&envelope, b"hunter3"),
+            Err(PngError::AuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_tampered_envelope() {
+        let mut envelope = encrypt(b"attack at dawn", b"hunter2").unwrap();
+        let last = envelope.len() - 1;
+        envelope[last] ^= 1;
+        assert!(matches!(
+            decrypt(&envelope, b"hunter2"),
+            Err(PngError::AuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_envelopes() {
+        assert!(matches!(
+            decrypt(b"plain text", b"hunter2"),
+            Err(PngError::InvalidEnvelope(_))
+        ));
+        assert!(matches!(
+            decrypt(b"\x01short", b"hunter2"),
+            Err(PngError::InvalidEnvelope(_))
+        ));
+
+        let mut envelope = encrypt(b"attack at dawn", b"hunter2").unwrap();
+        envelope[0] = 99;
+        assert!(matches!(
+            decrypt(&envelope, b"hunter2"),
+            Err(PngError::InvalidEnvelope(_))
+        ));
+    }
+}
@@ -0,0 +1,156 @@
+use std::fmt;
+use std::io;
+use std::str::Utf8Error;
+
+use crate::chunk_type::ChunkType;
+
+/// Errors produced while parsing, editing or serializing a PNG.
+#[derive(Debug)]
+pub enum PngError {
+    /// The first 8 bytes of the input are not the PNG signature.
+    InvalidSignature([u8; 8]),
+    /// The stored CRC of a chunk does not match the CRC computed from its type and data.
+    CrcMismatch {
+        chunk_type: ChunkType,
+        offset: u64,
+        expected: u32,
+        actual: u32,
+    },
+    /// The input ended before the chunk starting at `offset` was complete.
+    TruncatedChunk { offset: u64 },
+    /// The bytes are not a valid chunk type.
+    InvalidChunkType(Vec<u8>),
+    /// No chunk with the requested type exists.
+    ChunkNotFound(String),
+    /// A chunk was written after the IEND chunk, which must be the last chunk in a PNG.
+    ChunkAfterIend(ChunkType),
+    /// The IHDR chunk is missing fields or holds values the spec doesn't allow.
+    InvalidIhdr(String),
+    /// A tEXt, zTXt or iTXt chunk is malformed, or text can't be stored in one.
+    InvalidText(String),
+    /// The tIME chunk is malformed or holds an impossible date.
+    InvalidTime(String),
+    /// An encrypted message envelope is malformed or uses an unknown version.
+    InvalidEnvelope(String),
+    /// The message is encrypted but no password or key was supplied.
+    MissingSecret,
+    /// An encrypted message could not be decrypted with the given password or key.
+    AuthenticationFailed,
+    /// A binary payload envelope is malformed or uses an unknown version.
+    InvalidPayload(String),
+    /// The fragments of a split message are inconsistent or incomplete.
+    InvalidFragment(String),
+    /// An acTL, fcTL or fdAT chunk is malformed, or the frames don't fit together.
+    InvalidApng(String),
+    /// The IDAT stream can't be inflated or its scanlines don't match IHDR.
+    InvalidImageData(String),
+    /// The image uses a pixel format or interlacing that the operation doesn't support.
+    UnsupportedImage(String),
+    /// The message needs more space than the image can hold.
+    MessageTooLarge { size: usize, capacity: usize },
+    /// The PNG violates the chunk ordering rules in this many places.
+    ValidationFailed(usize),
+    /// Processing a batch of files failed for some of them.
+    BatchFailed { failed: usize, total: usize },
+    /// The operation needs a cargo feature that this build of pngme doesn't have.
+    FeatureDisabled(&'static str),
+    /// A command line argument is missing, or can't be combined with the others.
+    InvalidArgument(String),
+    /// The chunk data is not valid UTF-8.
+    InvalidUtf8(Utf8Error),
+    /// An underlying I/O operation failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for PngError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PngError::InvalidSignature(bytes) => {
+                write!(f, "invalid PNG signature {:?}", bytes)
+            }
+            PngError::CrcMismatch {
+                chunk_type,
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "CRC mismatch in {} chunk at offset {}: expected {:#010x}, found {:#010x}",
+                chunk_type, offset, expected, actual
+            ),
+            PngError::TruncatedChunk { offset } => {
+                write!(f, "chunk at offset {} is truncated", offset)
+            }
+            PngError::InvalidChunkType(bytes) => {
+                write!(f, "invalid chunk type \"{}\"", bytes.escape_ascii())
+            }
+            PngError::ChunkNotFound(chunk_type) => {
+                write!(f, "no {} chunk found", chunk_type)
+            }
+            PngError::ChunkAfterIend(chunk_type) => {
+                write!(f, "cannot write {} chunk after IEND", chunk_type)
+            }
+            PngError::InvalidIhdr(reason) => write!(f, "invalid IHDR chunk: {}", reason),
+            PngError::InvalidText(reason) => write!(f, "invalid text chunk: {}", reason),
+            PngError::InvalidTime(reason) => write!(f, "invalid tIME chunk: {}", reason),
+            PngError::InvalidEnvelope(reason) => {
+                write!(f, "invalid encrypted message: {}", reason)
+            }
+            PngError::MissingSecret => {
+                write!(f, "message is encrypted but no password or key was given")
+            }
+            PngError::AuthenticationFailed => write!(
+                f,
+                "authentication failed: wrong password or key, or the message was modified"
+            ),
+            PngError::InvalidPayload(reason) => write!(f, "invalid payload: {}", reason),
+            PngError::InvalidFragment(reason) => write!(f, "invalid message fragments: {}", reason),
+            PngError::InvalidApng(reason) => write!(f, "invalid animation: {}", reason),
+            PngError::InvalidImageData(reason) => write!(f, "invalid image data: {}", reason),
+            PngError::UnsupportedImage(reason) => write!(f, "unsupported image: {}", reason),
+            PngError::MessageTooLarge { size, capacity } => write!(
+                f,
+                "message is {} bytes but the image can only hold {} bytes",
+                size, capacity
+            ),
+            PngError::ValidationFailed(count) => {
+                write!(f, "PNG structure is invalid ({} violations)", count)
+            }
+            PngError::BatchFailed { failed, total } => {
+                write!(f, "{} of {} files failed", failed, total)
+            }
+            PngError::FeatureDisabled(feature) => write!(
+                f,
+                "this operation needs pngme to be built with the \"{}\" feature",
+                feature
+            ),
+            PngError::InvalidArgument(reason) => write!(f, "invalid argument: {}", reason),
+            PngError::InvalidUtf8(error) => write!(f, "chunk data is not valid UTF-8: {}", error),
+            PngError::Io(error) => write!(f, "I/O error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for PngError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PngError::InvalidUtf8(error) => Some(error),
+            PngError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PngError {
+    fn from(error: io::Error) -> Self {
+        PngError::Io(error)
+    }
+}
+
+impl From<Utf8Error> for PngError {
+    fn from(error: Utf8Error) -> Self {
+        PngError::InvalidUtf8(error)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, PngError>;
@@ -0,0 +1,171 @@
+use crate::error::{PngError, Result};
+
+/// Sequence number and fragment count.
+pub const HEADER_LENGTH: usize = 4 + 4;
+/// The largest chunk data length the PNG spec allows.
+pub const MAX_CHUNK_LENGTH: usize = (1 << 31) - 1;
+
+/// Splits `data` into fragments no larger than `max_chunk_size` bytes, each holding:
+///
+/// 1. Zero based sequence number *(4 bytes)*
+/// 2. Total number of fragments *(4 bytes)*
+/// 3. The next slice of `data`
+pub fn split(data: &[u8], max_chunk_size: usize) -> Result<Vec<Vec<u8>>> {
+    let max_chunk_size = max_chunk_size.min(MAX_CHUNK_LENGTH);
+    if max_chunk_size <= HEADER_LENGTH {
+        return Err(invalid_fragment(&format!(
+            "maximum chunk size must be larger than the {} byte fragment header",
+            HEADER_LENGTH
+        )));
+    }
+    let part_size = max_chunk_size - HEADER_LENGTH;
+    let total = data.len().div_ceil(part_size).max(1);
+    let total = u32::try_from(total)
+        .map_err(|_| invalid_fragment("too many fragments for the maximum chunk size"))?;
+
+    let mut fragments = Vec::with_capacity(total as usize);
+    for sequence in 0..total {
+        let start = sequence as usize * part_size;
+        let part = &data[start..(start + part_size).min(data.len())];
+        let mut fragment = Vec::with_capacity(HEADER_LENGTH + part.len());
+        fragment.extend_from_slice(&sequence.to_be_bytes());
+        fragment.extend_from_slice(&total.to_be_bytes());
+        fragment.extend_from_slice(part);
+        fragments.push(fragment);
+    }
+    Ok(fragments)
+}
+
+/// Reassembles fragments produced by `split`, in any order. Fails if a fragment is
+/// missing, repeated, or disagrees with the others about the fragment count.
+pub fn reassemble<'a, I>(fragments: I) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    let mut parts = fragments
+        .into_iter()
+        .map(|fragment| {
+            let (sequence, total) = parse_header(fragment)?;
+            Ok((sequence, total, &fragment[HEADER_LENGTH..]))
+        })
+        .collect::<Result<Vec<(u32, u32, &[u8])>>>()?;
+
+    let total = match parts.first() {
+        Some(&(_, total, _)) => total,
+        None => return Err(invalid_fragment("no fragments found")),
+    };
+    if let Some(&(_, other, _)) = parts.iter().find(|&&(_, other, _)| other != total) {
+        return Err(invalid_fragment(&format!(
+            "fragments disagree on the fragment count ({} and {})",
+            total, other
+        )));
+    }
+
+    parts.sort_by_key(|&(sequence, _, _)| sequence);
+    for (expected, &(sequence, _, _)) in (0..total).zip(parts.iter()) {
+        if sequence != expected {
+            let problem = if sequence < expected {
+                "appears more than once"
+            } else {
+                "is missing"
+            };
+            return Err(invalid_fragment(&format!(
+                "fragment {} of {} {}",
+                sequence.min(expected),
+                total,
+                problem
+            )));
+        }
+    }
+    if parts.len() != total as usize {
+        return Err(invalid_fragment(&format!(
+            "expected {} fragments, found {}",
+            total,
+            parts.len()
+        )));
+    }
+
+    Ok(parts
+        .into_iter()
+        .flat_map(|(_, _, part)| part.iter().copied())
+        .collect())
+}
+
+fn parse_header(fragment: &[u8]) -> Result<(u32, u32)> {
+    if fragment.len() < HEADER_LENGTH {
+        return Err(invalid_fragment("fragment header is truncated"));
+    }
+    let sequence = u32::from_be_bytes(fragment[0..4].try_into().expect("4 bytes"));
+    let total = u32::from_be_bytes(fragment[4..8].try_into().expect("4 bytes"));
+    if total == 0 {
+        return Err(invalid_fragment("fragment count is zero"));
+    }
+    Ok((sequence, total))
+}
+
+fn invalid_fragment(reason: &str) -> PngError {
+    PngError::InvalidFragment(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_data() -> Vec<u8> {
+        (0..1000u32).map(|n| (n % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_split_sizes() {
+        let fragments = split(&testing_data(), 112).unwrap();
+        assert_eq!(fragments.len(), 10);
+        assert!(fragments.iter().all(|fragment| fragment.len() <= 112));
+    }
+
+    #[test]
+    fn test_round_trip_out_of_order() {
+        let data = testing_data();
+        let mut fragments = split(&data, 300).unwrap();
+        fragments.reverse();
+        let reassembled = reassemble(fragments.iter().map(Vec::as_slice)).unwrap();
+        assert_eq!(reassembled, data);
+    }
+
+    #[test]
+    fn test_empty_data() {
+        let fragments = split(&[], 100).unwrap();
+        assert_eq!(fragments.len(), 1);
+        assert!(reassemble(fragments.iter().map(Vec::as_slice))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_missing_fragment() {
+        let mut fragments = split(&testing_data(), 300).unwrap();
+        fragments.remove(1);
+        let result = reassemble(fragments.iter().map(Vec::as_slice));
+        assert!(matches!(result, Err(PngError::InvalidFragment(_))));
+    }
+
+    #[test]
+    fn test_duplicate_fragment() {
+        let mut fragments = split(&testing_data(), 300).unwrap();
+        fragments.push(fragments[0].clone());
+        let result = reassemble(fragments.iter().map(Vec::as_slice));
+        assert!(matches!(result, Err(PngError::InvalidFragment(_))));
+    }
+
+    #[test]
+    fn test_mixed_sets() {
+        let mut fragments = split(&testing_data(), 300).unwrap();
+        fragments.extend(split(&testing_data(), 600).unwrap());
+        let result = reassemble(fragments.iter().map(Vec::as_slice));
+        assert!(matches!(result, Err(PngError::InvalidFragment(_))));
+    }
+
+    #[test]
+    fn test_max_size_too_small() {
+        assert!(split(&testing_data(), HEADER_LENGTH).is_err());
+    }
+}
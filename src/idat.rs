@@ -0,0 +1,614 @@
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::error::{PngError, Result};
+use crate::fragment::MAX_CHUNK_LENGTH;
+use crate::ihdr::{Ihdr, InterlaceMethod};
+use crate::pixels::{PixelFormat, Pixels};
+
+/// Inflates the zlib stream formed by the data of consecutive IDAT chunks. Fails as soon
+/// as the stream holds more than `limit` bytes, so a small stream can't inflate into an
+/// unbounded amount of memory.
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(data)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut inflated)
+        .map_err(|error| invalid_image_data(&format!("invalid zlib stream: {}", error)))?;
+    if inflated.len() > limit {
+        return Err(invalid_image_data(&format!(
+            "expected {} bytes of scanlines, found more",
+            limit
+        )));
+    }
+    Ok(inflated)
+}
+
+/// Compresses filtered scanlines into a zlib stream for IDAT at `level`, from 0 (store
+/// only) to 9 (smallest output).
+pub fn deflate(data: &[u8], level: u32) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+    encoder
+        .write_all(data)
+        .expect("writing to a Vec can't fail");
+    encoder.finish().expect("writing to a Vec can't fail")
+}
+
+/// How the encoder picks the filter applied to each scanline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterStrategy {
+    /// Filter type 0 on every scanline
+    None,
+    /// Filter type 1 on every scanline
+    Sub,
+    /// Filter type 2 on every scanline
+    Up,
+    /// Filter type 3 on every scanline
+    Average,
+    /// Filter type 4 on every scanline
+    Paeth,
+    /// Whichever filter gives the smallest sum of absolute differences, per scanline,
+    /// as recommended by the PNG spec
+    #[default]
+    Adaptive,
+}
+
+impl FilterStrategy {
+    /// The fixed filter type used on every scanline, or `None` for `Adaptive`.
+    fn filter_type(&self) -> Option<u8> {
+        match self {
+            FilterStrategy::None => Some(0),
+            FilterStrategy::Sub => Some(1),
+            FilterStrategy::Up => Some(2),
+            FilterStrategy::Average => Some(3),
+            FilterStrategy::Paeth => Some(4),
+            FilterStrategy::Adaptive => None,
+        }
+    }
+}
+
+/// Settings for re-encoding pixels into IDAT chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdatOptions {
+    filter: FilterStrategy,
+    compression_level: u32,
+    chunk_size: usize,
+}
+
+impl IdatOptions {
+    /// The IDAT chunk size most encoders use.
+    pub const DEFAULT_CHUNK_SIZE: usize = 8192;
+
+    /// Sets how scanline filters are chosen.
+    pub fn with_filter(mut self, filter: FilterStrategy) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sets the deflate level, from 0 (store only) to 9 (smallest output). Higher levels
+    /// are treated as 9.
+    pub fn with_compression_level(mut self, level: u32) -> Self {
+        self.compression_level = level.min(9);
+        self
+    }
+
+    /// Sets the largest amount of compressed data stored in one IDAT chunk, clamped to
+    /// the range a chunk can hold.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.clamp(1, MAX_CHUNK_LENGTH);
+        self
+    }
+
+    /// How scanline filters are chosen
+    pub fn filter(&self) -> FilterStrategy {
+        self.filter
+    }
+
+    /// The deflate level
+    pub fn compression_level(&self) -> u32 {
+        self.compression_level
+    }
+
+    /// The largest IDAT chunk written
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+}
+
+impl Default for IdatOptions {
+    fn default() -> Self {
+        IdatOptions {
+            filter: FilterStrategy::default(),
+            compression_level: Compression::default().level(),
+            chunk_size: IdatOptions::DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
+
+/// The Adam7 passes as (first column, first row, column step, row step).
+const ADAM7_PASSES: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Decodes the zlib stream formed by the data of consecutive IDAT chunks into
+/// non-interlaced `Pixels`, reversing the scanline filters and any Adam7 interlacing.
+pub fn decode(ihdr: &Ihdr, compressed: &[u8]) -> Result<Pixels> {
+    let format = ihdr.pixel_format();
+    let (width, height) = (ihdr.width(), ihdr.height());
+    let expected = match ihdr.interlace_method() {
+        InterlaceMethod::None => filtered_length(format, width, height)?,
+        InterlaceMethod::Adam7 => interlaced_length(format, width, height)?,
+    };
+    let filtered = inflate(compressed, expected)?;
+    check_length(&filtered, expected)?;
+    let data = match ihdr.interlace_method() {
+        InterlaceMethod::None => unfilter(format, width, height, &filtered)?,
+        InterlaceMethod::Adam7 => deinterlace(format, width, height, &filtered)?,
+    };
+    Pixels::new(width, height, format, data)
+}
+
+/// Unfilters each Adam7 pass of scanlines whose total length has been checked, and
+/// scatters its pixels into place in the full image.
+fn deinterlace(format: PixelFormat, width: u32, height: u32, filtered: &[u8]) -> Result<Vec<u8>> {
+    let passes: Vec<(u32, u32, u32, u32, u32, u32)> = ADAM7_PASSES
+        .iter()
+        .map(|&(x, y, x_step, y_step)| {
+            let pass_width = pass_size(width, x, x_step);
+            let pass_height = pass_size(height, y, y_step);
+            (x, y, x_step, y_step, pass_width, pass_height)
+        })
+        .collect();
+
+    let stride = format.stride(width);
+    let mut data = vec![0u8; stride * height as usize];
+    let mut rest = filtered;
+    for (x, y, x_step, y_step, pass_width, pass_height) in passes {
+        let (pass, remaining) = rest.split_at(filtered_length(format, pass_width, pass_height)?);
+        rest = remaining;
+        if pass.is_empty() {
+            continue;
+        }
+
+        let pass = unfilter(format, pass_width, pass_height, pass)?;
+        let pass_stride = format.stride(pass_width);
+        for (row, line) in pass.chunks_exact(pass_stride).enumerate() {
+            let target = (y + row as u32 * y_step) as usize * stride;
+            let target = &mut data[target..target + stride];
+            for column in 0..pass_width as usize {
+                let target_column = (x + column as u32 * x_step) as usize;
+                copy_pixel(format, line, column, target, target_column);
+            }
+        }
+    }
+    Ok(data)
+}
+
+/// The number of pixels along one side of an Adam7 pass.
+fn pass_size(size: u32, start: u32, step: u32) -> u32 {
+    size.saturating_sub(start).div_ceil(step)
+}
+
+/// The length of the filtered scanlines of all seven Adam7 passes.
+fn interlaced_length(format: PixelFormat, width: u32, height: u32) -> Result<usize> {
+    let mut length = 0usize;
+    for &(x, y, x_step, y_step) in &ADAM7_PASSES {
+        let pass_width = pass_size(width, x, x_step);
+        let pass_height = pass_size(height, y, y_step);
+        length = length
+            .checked_add(filtered_length(format, pass_width, pass_height)?)
+            .ok_or_else(|| invalid_image_data("image is too large"))?;
+    }
+    Ok(length)
+}
+
+/// The length of an image's filtered scanlines, including their filter type bytes.
+/// Empty Adam7 passes have no scanlines at all.
+fn filtered_length(format: PixelFormat, width: u32, height: u32) -> Result<usize> {
+    if width == 0 {
+        return Ok(0);
+    }
+    (format.stride(width) + 1)
+        .checked_mul(height as usize)
+        .ok_or_else(|| invalid_image_data("image is too large"))
+}
+
+fn check_length(filtered: &[u8], expected: usize) -> Result<()> {
+    if filtered.len() != expected {
+        return Err(invalid_image_data(&format!(
+            "expected {} bytes of scanlines, found {}",
+            expected,
+            filtered.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Reverses the filters of `height` scanlines whose total length has been checked.
+fn unfilter(format: PixelFormat, width: u32, height: u32, filtered: &[u8]) -> Result<Vec<u8>> {
+    let stride = format.stride(width);
+    let bpp = format.bytes_per_pixel();
+    let mut raw = vec![0u8; stride * height as usize];
+    let zero_line = vec![0u8; stride];
+    for (row, line) in filtered.chunks_exact(stride + 1).enumerate() {
+        let (previous, current) = raw.split_at_mut(row * stride);
+        let previous = match row {
+            0 => &zero_line[..],
+            _ => &previous[(row - 1) * stride..],
+        };
+        let current = &mut current[..stride];
+        current.copy_from_slice(&line[1..]);
+        unfilter_line(line[0], bpp, previous, current)?;
+    }
+    Ok(raw)
+}
+
+/// Copies pixel `column` of `source` to pixel `target_column` of `target`, both
+/// scanlines in `format`.
+fn copy_pixel(
+    format: PixelFormat,
+    source: &[u8],
+    column: usize,
+    target: &mut [u8],
+    target_column: usize,
+) {
+    let bits = format.bits_per_pixel();
+    if bits >= 8 {
+        let bytes = bits / 8;
+        target[target_column * bytes..(target_column + 1) * bytes]
+            .copy_from_slice(&source[column * bytes..(column + 1) * bytes]);
+    } else {
+        let mask = (1u8 << bits) - 1;
+        let source_shift = 8 - bits - column * bits % 8;
+        let value = (source[column * bits / 8] >> source_shift) & mask;
+        let target_shift = 8 - bits - target_column * bits % 8;
+        let byte = &mut target[target_column * bits / 8];
+        *byte = (*byte & !(mask << target_shift)) | (value << target_shift);
+    }
+}
+
+/// Applies the scanline filters chosen by `strategy`, prefixing each scanline with its
+/// filter type byte, ready for `deflate`.
+pub fn filter(pixels: &Pixels, strategy: FilterStrategy) -> Vec<u8> {
+    let stride = pixels.stride();
+    let bpp = pixels.format().bytes_per_pixel();
+    let mut filtered = Vec::with_capacity((stride + 1) * pixels.height() as usize);
+    let mut candidate = vec![0u8; stride];
+    let zero_line = vec![0u8; stride];
+    let mut previous = &zero_line[..];
+    for current in pixels.data().chunks_exact(stride) {
+        let filter_type = match strategy.filter_type() {
+            Some(filter_type) => filter_type,
+            None => (0..5)
+                .min_by_key(|&filter_type| {
+                    filter_line(filter_type, bpp, previous, current, &mut candidate);
+                    candidate
+                        .iter()
+                        .map(|&byte| (byte as i8).unsigned_abs() as u64)
+                        .sum::<u64>()
+                })
+                .expect("there are five filter types"),
+        };
+        filter_line(filter_type, bpp, previous, current, &mut candidate);
+        filtered.push(filter_type);
+        filtered.extend_from_slice(&candidate);
+        previous = current;
+    }
+    filtered
+}
+
+/// Encodes `pixels` as non-interlaced IDAT chunks according to `options`.
+pub fn encode(pixels: &Pixels, options: &IdatOptions) -> Vec<Chunk> {
+    let compressed = deflate(&filter(pixels, options.filter), options.compression_level);
+    compressed
+        .chunks(options.chunk_size)
+        .map(|data| Chunk::new(ChunkType::IDAT, data.to_vec()))
+        .collect()
+}
+
+/// Writes `current` filtered with `filter_type` into `filtered`.
+fn filter_line(filter_type: u8, bpp: usize, previous: &[u8], current: &[u8], filtered: &mut [u8]) {
+    for i in 0..current.len() {
+        let (left, upper_left) = if i >= bpp {
+            (current[i - bpp], previous[i - bpp])
+        } else {
+            (0, 0)
+        };
+        let prediction = match filter_type {
+            1 => left,
+            2 => previous[i],
+            3 => ((left as u16 + previous[i] as u16) / 2) as u8,
+            4 => paeth(left, previous[i], upper_left),
+            _ => 0,
+        };
+        filtered[i] = current[i].wrapping_sub(prediction);
+    }
+}
+
+fn unfilter_line(filter_type: u8, bpp: usize, previous: &[u8], current: &mut [u8]) -> Result<()> {
+    match filter_type {
+        // None
+        0 => {}
+        // Sub
+        1 => {
+            for i in bpp..current.len() {
+                current[i] = current[i].wrapping_add(current[i - bpp]);
+            }
+        }
+        // Up
+        2 => {
+            for (byte, above) in current.iter_mut().zip(previous) {
+                *byte = byte.wrapping_add(*above);
+            }
+        }
+        // Average
+        3 => {
+            for i in 0..current.len() {
+                let left = if i >= bpp { current[i - bpp] } else { 0 };
+                let average = ((left as u16 + previous[i] as u16) / 2) as u8;
+                current[i] = current[i].wrapping_add(average);
+            }
+        }
+        // Paeth
+        4 => {
+            for i in 0..current.len() {
+                let (left, upper_left) = if i >= bpp {
+                    (current[i - bpp], previous[i - bpp])
+                } else {
+                    (0, 0)
+                };
+                current[i] = current[i].wrapping_add(paeth(left, previous[i], upper_left));
+            }
+        }
+        other => {
+            return Err(invalid_image_data(&format!(
+                "unknown filter type {}",
+                other
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// The Paeth predictor from the PNG spec: whichever neighbour is closest to
+/// `left + above - upper_left`.
+fn paeth(left: u8, above: u8, upper_left: u8) -> u8 {
+    let estimate = left as i16 + above as i16 - upper_left as i16;
+    let distance_left = (estimate - left as i16).abs();
+    let distance_above = (estimate - above as i16).abs();
+    let distance_upper_left = (estimate - upper_left as i16).abs();
+    if distance_left <= distance_above && distance_left <= distance_upper_left {
+        left
+    } else if distance_above <= distance_upper_left {
+        above
+    } else {
+        upper_left
+    }
+}
+
+fn invalid_image_data(reason: &str) -> PngError {
+    PngError::InvalidImageData(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::ColorType;
+
+    fn rgb_ihdr(width: u32, height: u32) -> Ihdr {
+        Ihdr::new(width, height, 8, ColorType::Rgb, InterlaceMethod::None).unwrap()
+    }
+
+    /// Lays `raw` out as Adam7 passes using filter type None.
+    fn interlace(format: PixelFormat, width: u32, height: u32, raw: &[u8]) -> Vec<u8> {
+        let stride = format.stride(width);
+        let mut filtered = Vec::new();
+        for &(x, y, x_step, y_step) in &ADAM7_PASSES {
+            let pass_width = pass_size(width, x, x_step);
+            let pass_height = pass_size(height, y, y_step);
+            if pass_width == 0 {
+                continue;
+            }
+            for row in 0..pass_height {
+                let source = (y + row * y_step) as usize * stride;
+                let source = &raw[source..source + stride];
+                let mut line = vec![0u8; format.stride(pass_width)];
+                for column in 0..pass_width as usize {
+                    let source_column = (x + column as u32 * x_step) as usize;
+                    copy_pixel(format, source, source_column, &mut line, column);
+                }
+                filtered.push(0);
+                filtered.extend(line);
+            }
+        }
+        filtered
+    }
+
+    #[test]
+    fn test_unfilter_each_filter_type() {
+        let format = PixelFormat::new(ColorType::Rgb, 8).unwrap();
+        #[rustfmt::skip]
+        let filtered = [
+            0, 10, 20, 30, 40, 50, 60,
+            1, 10, 20, 30, 30, 30, 30,
+            2, 1, 1, 1, 1, 1, 1,
+            3, 5, 5, 5, 0, 0, 0,
+            4, 1, 1, 1, 1, 1, 1,
+        ];
+        #[rustfmt::skip]
+        let expected = [
+            10, 20, 30, 40, 50, 60,
+            10, 20, 30, 40, 50, 60,
+            11, 21, 31, 41, 51, 61,
+            10, 15, 20, 25, 33, 40,
+            11, 16, 21, 26, 34, 41,
+        ];
+        assert_eq!(unfilter(format, 2, 5, &filtered).unwrap(), expected);
+    }
+
+    fn gradient(width: u32, height: u32) -> Pixels {
+        let format = PixelFormat::new(ColorType::Rgb, 8).unwrap();
+        let data = (0..height)
+            .flat_map(|y| (0..width * 3).map(move |x| (x * 2 + y * 3) as u8))
+            .collect();
+        Pixels::new(width, height, format, data).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let ihdr = rgb_ihdr(40, 30);
+        let pixels = gradient(40, 30);
+        let strategies = [
+            FilterStrategy::None,
+            FilterStrategy::Sub,
+            FilterStrategy::Up,
+            FilterStrategy::Average,
+            FilterStrategy::Paeth,
+            FilterStrategy::Adaptive,
+        ];
+        for strategy in strategies {
+            let options = IdatOptions::default().with_filter(strategy);
+            let compressed: Vec<u8> = encode(&pixels, &options)
+                .iter()
+                .flat_map(|chunk| chunk.data().to_vec())
+                .collect();
+            assert_eq!(
+                decode(&ihdr, &compressed).unwrap(),
+                pixels,
+                "{:?}",
+                strategy
+            );
+        }
+    }
+
+    #[test]
+    fn test_fixed_filter_types() {
+        let pixels = gradient(4, 3);
+        let filtered = filter(&pixels, FilterStrategy::Paeth);
+        assert!(filtered.chunks_exact(13).all(|line| line[0] == 4));
+    }
+
+    #[test]
+    fn test_adaptive_filter_beats_none() {
+        let pixels = gradient(64, 64);
+        let sum = |filtered: Vec<u8>| -> u64 {
+            filtered
+                .chunks_exact(pixels.stride() + 1)
+                .flat_map(|line| {
+                    line[1..]
+                        .iter()
+                        .map(|&byte| (byte as i8).unsigned_abs() as u64)
+                })
+                .sum()
+        };
+        let adaptive = sum(filter(&pixels, FilterStrategy::Adaptive));
+        assert!(adaptive < sum(filter(&pixels, FilterStrategy::None)));
+        assert!(adaptive <= sum(filter(&pixels, FilterStrategy::Sub)));
+    }
+
+    #[test]
+    fn test_encode_chunk_size() {
+        let pixels = gradient(40, 30);
+        let options = IdatOptions::default()
+            .with_compression_level(0)
+            .with_chunk_size(1000);
+        let chunks = encode(&pixels, &options);
+        assert!(chunks.len() > 3);
+        assert!(chunks.iter().all(|chunk| chunk.length() <= 1000));
+        assert!(chunks
+            .iter()
+            .all(|chunk| *chunk.chunk_type() == ChunkType::IDAT));
+    }
+
+    #[test]
+    fn test_decode_adam7() {
+        let formats = [
+            (8, ColorType::Rgba),
+            (16, ColorType::Grayscale),
+            (1, ColorType::Grayscale),
+            (4, ColorType::Indexed),
+        ];
+        for (bit_depth, color_type) in formats {
+            for (width, height) in [(1, 1), (9, 5), (3, 10), (16, 16)] {
+                let ihdr = Ihdr::new(width, height, bit_depth, color_type, InterlaceMethod::Adam7)
+                    .unwrap();
+                let format = ihdr.pixel_format();
+                let raw: Vec<u8> = (0..format.stride(width) * height as usize)
+                    .map(|n| (n * 37 + 11) as u8)
+                    .collect();
+                // Padding bits at the end of a scanline aren't part of any pixel
+                let mut expected = raw.clone();
+                let used_bits = width as usize * format.bits_per_pixel() % 8;
+                if used_bits != 0 {
+                    for line in expected.chunks_exact_mut(format.stride(width)) {
+                        *line.last_mut().unwrap() &= !(0xff >> used_bits);
+                    }
+                }
+
+                let stream = deflate(&interlace(format, width, height, &raw), 6);
+                let pixels = decode(&ihdr, &stream).unwrap();
+                assert_eq!(
+                    pixels.data(),
+                    expected,
+                    "{}x{} {} bit {}",
+                    width,
+                    height,
+                    bit_depth,
+                    color_type
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_paeth() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 20), 10);
+    }
+
+    #[test]
+    fn test_invalid_scanlines() {
+        let ihdr = rgb_ihdr(2, 2);
+        assert!(matches!(
+            decode(&ihdr, &deflate(&[0; 13], 6)),
+            Err(PngError::InvalidImageData(_))
+        ));
+        let mut unknown_filter = [0; 14];
+        unknown_filter[7] = 5;
+        assert!(matches!(
+            decode(&ihdr, &deflate(&unknown_filter, 6)),
+            Err(PngError::InvalidImageData(_))
+        ));
+        assert!(inflate(b"not zlib", 100).is_err());
+
+        let interlaced = Ihdr::new(2, 2, 8, ColorType::Rgb, InterlaceMethod::Adam7).unwrap();
+        assert!(decode(&interlaced, &deflate(&[0; 14], 6)).is_err());
+    }
+
+    #[test]
+    fn test_inflate_is_bounded() {
+        let bomb = deflate(&vec![0; 1 << 20], 9);
+        assert!(bomb.len() < 1 << 12);
+        assert!(matches!(
+            inflate(&bomb, 1 << 10),
+            Err(PngError::InvalidImageData(_))
+        ));
+        assert_eq!(inflate(&bomb, 1 << 20).unwrap().len(), 1 << 20);
+        assert!(matches!(
+            decode(&rgb_ihdr(2, 2), &bomb),
+            Err(PngError::InvalidImageData(_))
+        ));
+    }
+}
@@ -0,0 +1,412 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::error::{PngError, Result};
+use crate::pixels::PixelFormat;
+
+/// The color type of an image, as stored in IHDR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    /// The value used for this color type in the IHDR chunk.
+    pub fn code(&self) -> u8 {
+        match self {
+            ColorType::Grayscale => 0,
+            ColorType::Rgb => 2,
+            ColorType::Indexed => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::Rgba => 6,
+        }
+    }
+
+    /// The bit depths the PNG spec allows for this color type.
+    pub fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[8, 16],
+        }
+    }
+
+    /// The number of samples per pixel.
+    pub fn channels(&self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    /// Whether the last sample of each pixel is an alpha channel.
+    pub fn has_alpha(&self) -> bool {
+        matches!(self, ColorType::GrayscaleAlpha | ColorType::Rgba)
+    }
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = PngError;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Rgb),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::Rgba),
+            _ => Err(PngError::InvalidIhdr(format!(
+                "unknown color type {}",
+                value
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ColorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorType::Grayscale => "Grayscale",
+            ColorType::Rgb => "RGB",
+            ColorType::Indexed => "Indexed",
+            ColorType::GrayscaleAlpha => "Grayscale + Alpha",
+            ColorType::Rgba => "RGBA",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The interlace method of an image, as stored in IHDR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum InterlaceMethod {
+    None,
+    Adam7,
+}
+
+impl fmt::Display for InterlaceMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterlaceMethod::None => write!(f, "None"),
+            InterlaceMethod::Adam7 => write!(f, "Adam7"),
+        }
+    }
+}
+
+/// The parsed contents of an IHDR chunk. See the PNG Spec for more details
+/// http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.IHDR
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Ihdr {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: ColorType,
+    compression_method: u8,
+    filter_method: u8,
+    interlace_method: InterlaceMethod,
+}
+
+impl Ihdr {
+    /// The length of the IHDR chunk data in bytes.
+    pub const LENGTH: usize = 13;
+
+    /// Creates an `Ihdr` using the only compression and filter methods the spec defines.
+    /// Returns an error if the dimensions or bit depth / color type combination are invalid.
+    pub fn new(
+        width: u32,
+        height: u32,
+        bit_depth: u8,
+        color_type: ColorType,
+        interlace_method: InterlaceMethod,
+    ) -> Result<Ihdr> {
+        let ihdr = Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method,
+        };
+        ihdr.check()?;
+        Ok(ihdr)
+    }
+
+    /// The image width in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The image height in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The number of bits per sample, or per palette index for indexed images
+    pub fn bit_depth(&self) -> u8 {
+        self.bit_depth
+    }
+
+    /// The `ColorType` of the image
+    pub fn color_type(&self) -> ColorType {
+        self.color_type
+    }
+
+    /// The compression method. Always 0 (deflate) for a valid PNG.
+    pub fn compression_method(&self) -> u8 {
+        self.compression_method
+    }
+
+    /// The filter method. Always 0 (adaptive filtering) for a valid PNG.
+    pub fn filter_method(&self) -> u8 {
+        self.filter_method
+    }
+
+    /// The `InterlaceMethod` of the image
+    pub fn interlace_method(&self) -> InterlaceMethod {
+        self.interlace_method
+    }
+
+    /// The `PixelFormat` of the image's scanlines
+    pub fn pixel_format(&self) -> PixelFormat {
+        PixelFormat::from(self)
+    }
+
+    /// The number of bytes in one unfiltered scanline, without the filter type byte.
+    pub fn stride(&self) -> usize {
+        self.pixel_format().stride(self.width)
+    }
+
+    /// Returns the 13 data bytes of the IHDR chunk described by this `Ihdr`.
+    pub fn as_bytes(&self) -> [u8; Ihdr::LENGTH] {
+        let mut bytes = [0u8; Ihdr::LENGTH];
+        bytes[0..4].copy_from_slice(&self.width.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.height.to_be_bytes());
+        bytes[8] = self.bit_depth;
+        bytes[9] = self.color_type.code();
+        bytes[10] = self.compression_method;
+        bytes[11] = self.filter_method;
+        bytes[12] = match self.interlace_method {
+            InterlaceMethod::None => 0,
+            InterlaceMethod::Adam7 => 1,
+        };
+        bytes
+    }
+
+    /// Returns an IHDR `Chunk` holding this `Ihdr`.
+    pub fn to_chunk(self) -> Chunk {
+        let chunk_type = ChunkType::IHDR;
+        Chunk::new(chunk_type, self.as_bytes().to_vec())
+    }
+
+    fn check(&self) -> Result<()> {
+        const MAX_DIMENSION: u32 = (1 << 31) - 1;
+        if self.width == 0 || self.width > MAX_DIMENSION {
+            return Err(PngError::InvalidIhdr(format!(
+                "invalid width {}",
+                self.width
+            )));
+        }
+        if self.height == 0 || self.height > MAX_DIMENSION {
+            return Err(PngError::InvalidIhdr(format!(
+                "invalid height {}",
+                self.height
+            )));
+        }
+        if !self
+            .color_type
+            .allowed_bit_depths()
+            .contains(&self.bit_depth)
+        {
+            return Err(PngError::InvalidIhdr(format!(
+                "bit depth {} is not allowed for color type {}",
+                self.bit_depth, self.color_type
+            )));
+        }
+        if self.compression_method != 0 {
+            return Err(PngError::InvalidIhdr(format!(
+                "unknown compression method {}",
+                self.compression_method
+            )));
+        }
+        if self.filter_method != 0 {
+            return Err(PngError::InvalidIhdr(format!(
+                "unknown filter method {}",
+                self.filter_method
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<&[u8]> for Ihdr {
+    type Error = PngError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Ihdr::LENGTH {
+            return Err(PngError::InvalidIhdr(format!(
+                "expected {} bytes, found {}",
+                Ihdr::LENGTH,
+                bytes.len()
+            )));
+        }
+
+        let interlace_method = match bytes[12] {
+            0 => InterlaceMethod::None,
+            1 => InterlaceMethod::Adam7,
+            other => {
+                return Err(PngError::InvalidIhdr(format!(
+                    "unknown interlace method {}",
+                    other
+                )))
+            }
+        };
+        let ihdr = Ihdr {
+            width: u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            height: u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            bit_depth: bytes[8],
+            color_type: ColorType::try_from(bytes[9])?,
+            compression_method: bytes[10],
+            filter_method: bytes[11],
+            interlace_method,
+        };
+        ihdr.check()?;
+        Ok(ihdr)
+    }
+}
+
+impl TryFrom<&Chunk> for Ihdr {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if *chunk.chunk_type() != ChunkType::IHDR {
+            return Err(PngError::InvalidIhdr(format!(
+                "expected an IHDR chunk, found {}",
+                chunk.chunk_type()
+            )));
+        }
+        Ihdr::try_from(chunk.data())
+    }
+}
+
+impl fmt::Display for Ihdr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Ihdr {{")?;
+        writeln!(f, "  Width: {}", self.width())?;
+        writeln!(f, "  Height: {}", self.height())?;
+        writeln!(f, "  Bit depth: {}", self.bit_depth())?;
+        writeln!(f, "  Color type: {}", self.color_type())?;
+        writeln!(f, "  Compression method: {}", self.compression_method())?;
+        writeln!(f, "  Filter method: {}", self.filter_method())?;
+        writeln!(f, "  Interlace method: {}", self.interlace_method())?;
+        writeln!(f, "}}")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ihdr_bytes(bit_depth: u8, color_type: u8) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&50u32.to_be_bytes());
+        bytes.extend_from_slice(&20u32.to_be_bytes());
+        bytes.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_ihdr() {
+        let ihdr = Ihdr::try_from(ihdr_bytes(8, 6).as_slice()).unwrap();
+        assert_eq!(ihdr.width(), 50);
+        assert_eq!(ihdr.height(), 20);
+        assert_eq!(ihdr.bit_depth(), 8);
+        assert_eq!(ihdr.color_type(), ColorType::Rgba);
+        assert_eq!(ihdr.compression_method(), 0);
+        assert_eq!(ihdr.filter_method(), 0);
+        assert_eq!(ihdr.interlace_method(), InterlaceMethod::None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let ihdr = Ihdr::new(7, 3, 16, ColorType::Rgb, InterlaceMethod::Adam7).unwrap();
+        let parsed = Ihdr::try_from(&ihdr.to_chunk()).unwrap();
+        assert_eq!(parsed, ihdr);
+    }
+
+    #[test]
+    fn test_stride() {
+        let rgba = Ihdr::new(50, 1, 8, ColorType::Rgba, InterlaceMethod::None).unwrap();
+        assert_eq!(rgba.pixel_format().bits_per_pixel(), 32);
+        assert_eq!(rgba.stride(), 200);
+
+        let rgb16 = Ihdr::new(3, 1, 16, ColorType::Rgb, InterlaceMethod::None).unwrap();
+        assert_eq!(rgb16.stride(), 18);
+
+        let mono = Ihdr::new(9, 1, 1, ColorType::Grayscale, InterlaceMethod::None).unwrap();
+        assert_eq!(mono.stride(), 2);
+    }
+
+    #[test]
+    fn test_bit_depth_color_type_combinations() {
+        let valid = [
+            (1, 0),
+            (2, 0),
+            (4, 0),
+            (8, 0),
+            (16, 0),
+            (8, 2),
+            (16, 2),
+            (1, 3),
+            (2, 3),
+            (4, 3),
+            (8, 3),
+            (8, 4),
+            (16, 4),
+            (8, 6),
+            (16, 6),
+        ];
+        for bit_depth in [1, 2, 3, 4, 8, 16, 32] {
+            for color_type in 0..8 {
+                let result = Ihdr::try_from(ihdr_bytes(bit_depth, color_type).as_slice());
+                assert_eq!(
+                    result.is_ok(),
+                    valid.contains(&(bit_depth, color_type)),
+                    "bit depth {} color type {}",
+                    bit_depth,
+                    color_type
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_ihdr() {
+        let mut zero_width = ihdr_bytes(8, 6);
+        zero_width[..4].copy_from_slice(&[0, 0, 0, 0]);
+        assert!(Ihdr::try_from(zero_width.as_slice()).is_err());
+
+        let mut bad_interlace = ihdr_bytes(8, 6);
+        bad_interlace[12] = 2;
+        assert!(Ihdr::try_from(bad_interlace.as_slice()).is_err());
+
+        assert!(matches!(
+            Ihdr::try_from(&ihdr_bytes(8, 6)[..12]),
+            Err(PngError::InvalidIhdr(_))
+        ));
+    }
+}
@@ -0,0 +1,90 @@
+//! Reading, editing and writing PNG files chunk by chunk, and hiding messages in them.
+//!
+//! A [`Png`] is a list of [`Chunk`]s. It can be parsed from a file or any reader, edited,
+//! checked against the PNG chunk ordering rules and written back out. For read-only
+//! scans, a [`PngRef`] borrows its [`ChunkRef`]s from a buffer instead of copying them
+//! and only checks CRCs on request. The [`message`]
+//! module hides text or binary payloads in their own chunks, in text chunks or in the
+//! pixel data, optionally encrypted.
+//!
+//! ```
+//! use pngme::message::{self, EncodeOptions, Location, Message};
+//! use pngme::{Chunk, ChunkType, Png};
+//! use std::str::FromStr;
+//!
+//! let mut png = Png::from_chunks(vec![Chunk::new(ChunkType::IEND, Vec::new())]);
+//! let location = Location::Chunk(ChunkType::from_str("ruSt")?);
+//! let message = Message::Text("hello".to_string());
+//! message::encode(&mut png, &location, message, &EncodeOptions::default())?;
+//!
+//! let bytes = png.as_bytes();
+//! let png = Png::try_from(bytes.as_slice())?;
+//! assert_eq!(message::decode(&png, &location, None)?, b"hello");
+//! # Ok::<(), pngme::PngError>(())
+//! ```
+//!
+//! # Features
+//!
+//! - `crypto` *(default)*: [`crypto`], password and key file encryption of messages
+//! - `mmap` *(default)*: [`mapped::MappedFile`], for reading large files without loading
+//!   them into memory
+//! - `serde`: `Serialize` for chunk types, headers, validation results and [`report`]s
+//! - `cli` *(default)*: the `pngme` command line tool
+
+/// Animated PNG chunks (acTL, fcTL, fdAT) and frames
+pub mod apng;
+/// Writing files so that a failure never leaves them half written
+pub mod atomic;
+/// A single PNG chunk and its CRC
+pub mod chunk;
+/// A chunk borrowed from an in-memory buffer
+pub mod chunk_ref;
+/// Four letter chunk type codes and their property bits
+pub mod chunk_type;
+/// Encrypted message envelopes
+#[cfg(feature = "crypto")]
+pub mod crypto;
+/// The error type shared by the whole crate
+pub mod error;
+/// Splitting messages across several chunks and joining them again
+pub mod fragment;
+/// Compressing and filtering pixel data into IDAT chunks, and back
+pub mod idat;
+/// The image header chunk (IHDR)
+pub mod ihdr;
+/// Hiding messages in the least significant bits of pixel samples
+pub mod lsb;
+/// Read-only memory-mapped files
+#[cfg(feature = "mmap")]
+pub mod mapped;
+/// Hiding messages in a PNG and recovering them
+pub mod message;
+/// Options for reading damaged PNGs, and the problems found while doing so
+pub mod parse;
+/// Binary payloads that keep their file name and MIME type
+pub mod payload;
+/// Decoded pixel data
+pub mod pixels;
+/// A whole PNG file
+pub mod png;
+/// A whole PNG file borrowed from an in-memory buffer
+pub mod png_ref;
+/// Reading chunks one at a time from a stream
+pub mod reader;
+/// Summaries of a PNG's chunks for display or serialization
+pub mod report;
+/// Textual metadata chunks (tEXt, zTXt, iTXt)
+pub mod text;
+/// The last modification time chunk (tIME)
+pub mod time;
+/// The PNG chunk ordering rules
+pub mod validate;
+/// Writing chunks one at a time to a stream
+pub mod writer;
+
+pub use chunk::Chunk;
+pub use chunk_ref::ChunkRef;
+pub use chunk_type::ChunkType;
+pub use error::{PngError, Result};
+pub use png::{ChunkPosition, Png};
+pub use png_ref::PngRef;
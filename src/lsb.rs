@@ -0,0 +1,179 @@
+use crate::error::{PngError, Result};
+use crate::ihdr::{ColorType, Ihdr};
+
+/// Bytes used to store the message length ahead of the message.
+const LENGTH_HEADER: usize = 4;
+
+/// The number of message bytes that fit in the least significant bits of an image
+/// described by `ihdr`.
+pub fn capacity(ihdr: &Ihdr) -> Result<usize> {
+    Ok((carrier_indices(ihdr)?.count() / 8).saturating_sub(LENGTH_HEADER))
+}
+
+/// Hides `message` in the least significant bit of every color sample of `raw`, the
+/// unfiltered scanlines of an image described by `ihdr`. The message is preceded by its
+/// length as a big endian u32 and written most significant bit first.
+///
+/// Alpha samples are left alone, since changing them can reveal fully transparent pixels.
+pub fn embed(ihdr: &Ihdr, raw: &mut [u8], message: &[u8]) -> Result<()> {
+    check_length(ihdr, raw)?;
+    let capacity = capacity(ihdr)?;
+    if message.len() > capacity {
+        return Err(PngError::MessageTooLarge {
+            size: message.len(),
+            capacity,
+        });
+    }
+
+    let length = (message.len() as u32).to_be_bytes();
+    let bits = length
+        .iter()
+        .chain(message)
+        .flat_map(|byte| (0..8).rev().map(move |shift| (byte >> shift) & 1));
+    for (index, bit) in carrier_indices(ihdr)?.zip(bits) {
+        raw[index] = (raw[index] & !1) | bit;
+    }
+    Ok(())
+}
+
+/// Recovers a message hidden by `embed`.
+pub fn extract(ihdr: &Ihdr, raw: &[u8]) -> Result<Vec<u8>> {
+    check_length(ihdr, raw)?;
+    let capacity = capacity(ihdr)?;
+    let bits: Vec<u8> = carrier_indices(ihdr)?.map(|index| raw[index] & 1).collect();
+    let mut bytes = bits
+        .chunks_exact(8)
+        .map(|bits| bits.iter().fold(0u8, |byte, &bit| (byte << 1) | bit));
+
+    let length: Vec<u8> = bytes.by_ref().take(LENGTH_HEADER).collect();
+    let length = match <[u8; LENGTH_HEADER]>::try_from(length) {
+        Ok(length) => u32::from_be_bytes(length) as usize,
+        Err(_) => return Err(no_message()),
+    };
+    if length > capacity {
+        return Err(no_message());
+    }
+    Ok(bytes.take(length).collect())
+}
+
+/// Indices into the raw scanlines of the bytes whose lowest bit can carry data: the
+/// least significant byte of every non-alpha sample.
+fn carrier_indices(ihdr: &Ihdr) -> Result<impl Iterator<Item = usize>> {
+    let color_type = ihdr.color_type();
+    if color_type == ColorType::Indexed {
+        return Err(PngError::UnsupportedImage(
+            "indexed images can't hold an LSB message".to_string(),
+        ));
+    }
+    if ihdr.bit_depth() < 8 {
+        return Err(PngError::UnsupportedImage(format!(
+            "bit depth {} is too low to hold an LSB message",
+            ihdr.bit_depth()
+        )));
+    }
+
+    let bytes_per_sample = ihdr.bit_depth() as usize / 8;
+    let bytes_per_pixel = color_type.channels() * bytes_per_sample;
+    let color_samples = color_type.channels() - color_type.has_alpha() as usize;
+    let pixels = ihdr.width() as usize * ihdr.height() as usize;
+    Ok((0..pixels).flat_map(move |pixel| {
+        (0..color_samples).map(move |sample| {
+            pixel * bytes_per_pixel + sample * bytes_per_sample + bytes_per_sample - 1
+        })
+    }))
+}
+
+fn check_length(ihdr: &Ihdr, raw: &[u8]) -> Result<()> {
+    let expected = ihdr.stride() * ihdr.height() as usize;
+    if raw.len() != expected {
+        return Err(PngError::InvalidImageData(format!(
+            "expected {} bytes of pixels, found {}",
+            expected,
+            raw.len()
+        )));
+    }
+    Ok(())
+}
+
+fn no_message() -> PngError {
+    PngError::InvalidPayload("no LSB message found in the image".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::InterlaceMethod;
+
+    fn image(width: u32, height: u32, bit_depth: u8, color_type: ColorType) -> (Ihdr, Vec<u8>) {
+        let ihdr = Ihdr::new(width, height, bit_depth, color_type, InterlaceMethod::None).unwrap();
+        let raw = (0..ihdr.stride() * height as usize)
+            .map(|n| (n * 7) as u8)
+            .collect();
+        (ihdr, raw)
+    }
+
+    #[test]
+    fn test_capacity() {
+        let (rgba, _) = image(10, 10, 8, ColorType::Rgba);
+        assert_eq!(capacity(&rgba).unwrap(), 300 / 8 - 4);
+
+        let (gray16, _) = image(16, 2, 16, ColorType::Grayscale);
+        assert_eq!(capacity(&gray16).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (ihdr, mut raw) = image(20, 20, 8, ColorType::Rgba);
+        let original = raw.clone();
+        embed(&ihdr, &mut raw, b"hidden in plain sight").unwrap();
+        assert_eq!(extract(&ihdr, &raw).unwrap(), b"hidden in plain sight");
+
+        for (index, (&before, &after)) in original.iter().zip(&raw).enumerate() {
+            assert!(before ^ after <= 1);
+            if index % 4 == 3 {
+                assert_eq!(before, after, "alpha sample {} changed", index);
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_16_bit() {
+        let (ihdr, mut raw) = image(8, 8, 16, ColorType::Rgb);
+        let original = raw.clone();
+        embed(&ihdr, &mut raw, b"deep").unwrap();
+        assert_eq!(extract(&ihdr, &raw).unwrap(), b"deep");
+        for index in (0..raw.len()).step_by(2) {
+            assert_eq!(original[index], raw[index]);
+        }
+    }
+
+    #[test]
+    fn test_message_too_large() {
+        let (ihdr, mut raw) = image(4, 4, 8, ColorType::Rgb);
+        assert!(matches!(
+            embed(&ihdr, &mut raw, &[0; 3]),
+            Err(PngError::MessageTooLarge {
+                size: 3,
+                capacity: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_images() {
+        let (indexed, _) = image(4, 4, 8, ColorType::Indexed);
+        assert!(matches!(
+            capacity(&indexed),
+            Err(PngError::UnsupportedImage(_))
+        ));
+        let (mono, _) = image(4, 4, 1, ColorType::Grayscale);
+        assert!(capacity(&mono).is_err());
+    }
+
+    #[test]
+    fn test_no_message() {
+        let (ihdr, _) = image(4, 4, 8, ColorType::Rgb);
+        let raw = vec![0xff; ihdr.stride() * 4];
+        assert!(extract(&ihdr, &raw).is_err());
+    }
+}
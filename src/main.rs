@@ -1,178 +1,521 @@
-use std::{ffi::OsString, fs::File, io::Write, str::FromStr};
+use std::{
+    ffi::{OsStr, OsString},
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+    process::ExitCode,
+    str::FromStr,
+};
 
 use args::Args;
-use chunk::Chunk;
-use chunk_type::ChunkType;
+use batch::Input;
 use clap::Parser;
-use png::Png;
+use commands::{Commands, Format, Method, SecretArgs};
+use output::Output;
+use pngme::atomic;
+use pngme::lsb;
+#[cfg(feature = "mmap")]
+use pngme::mapped::MappedFile;
+use pngme::message::{self, EncodeOptions, Location, Message};
+use pngme::parse::ParseOptions;
+use pngme::payload::Payload;
+use pngme::report::{MessageReport, PngReport, ValidationReport};
+use pngme::text::is_text_chunk_type;
+use pngme::{ChunkType, Png, PngError, PngRef, Result};
 
 mod args;
-mod chunk;
-mod chunk_type;
+mod batch;
 mod commands;
-mod png;
+mod output;
 
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Error>;
-
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let cli = Args::parse();
+    let out = Output::new(cli.verbosity(), cli.format);
+
+    match run(cli.command, cli.recursive, cli.jobs, &out) {
+        Ok(()) => ExitCode::SUCCESS,
+        // The reader went away, e.g. `pngme print image.png | head`, which isn't a failure
+        Err(PngError::Io(error)) if error.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(error) => {
+            out.error(&error);
+            ExitCode::from(output::exit_code(&error))
+        }
+    }
+}
 
-    match cli.command {
-        commands::Commands::Encode {
+fn run(command: Commands, recursive: bool, jobs: Option<usize>, out: &Output) -> Result<()> {
+    let input = |file_path| Input::resolve(file_path, recursive);
+    match command {
+        Commands::Encode {
             file_path,
             chunk_type,
             message,
             output_file,
+            output,
+            input_file,
+            position,
+            keyword,
+            secret,
+            max_chunk_size,
+            method,
+            backup,
         } => {
-            let result = encode(file_path, chunk_type, message, output_file);
-            match result {
-                Ok(_) => (),
-                Err(error) => panic!("Unable to encode {}", error),
+            let input = input(file_path)?;
+            let output_file = output.or(output_file);
+            check_single_output(&input, &output_file, "--output")?;
+            check_max_chunk_size(max_chunk_size)?;
+            let mut options = EncodeOptions::default()
+                .with_position(position.into())
+                .with_max_chunk_size(max_chunk_size);
+            if let Some(secret) = load_secret(secret)? {
+                options = options.with_secret(secret);
             }
+            let location = location(&chunk_type, keyword, method)?;
+            let message = read_message(message, input_file)?;
+            batch::run(input, jobs, out, |file_path, out| {
+                encode(
+                    file_path,
+                    &location,
+                    message.clone(),
+                    output_file.clone(),
+                    &options,
+                    backup.backup.clone(),
+                    out,
+                )
+            })
         }
-        commands::Commands::Decode {
+        Commands::Decode {
             file_path,
             chunk_type,
+            keyword,
+            secret,
+            output_file,
+            method,
         } => {
-            let result = decode(file_path, chunk_type);
-            match result {
-                Ok(_) => (),
-                Err(error) => panic!("Unable to decode {}", error),
-            }
+            let input = input(file_path)?;
+            check_single_output(&input, &output_file, "--output-file")?;
+            let location = location(&chunk_type, keyword, method)?;
+            let secret = load_secret(secret)?;
+            batch::run(input, jobs, out, |file_path, out| {
+                decode(
+                    file_path,
+                    &location,
+                    secret.clone(),
+                    output_file.clone(),
+                    out,
+                )
+            })
         }
-        commands::Commands::Remove {
+        Commands::Remove {
             file_path,
             chunk_type,
+            keyword,
+            backup,
+        } => batch::run(input(file_path)?, jobs, out, |file_path, out| {
+            remove(
+                file_path,
+                chunk_type.clone(),
+                keyword.clone(),
+                backup.backup.clone(),
+                out,
+            )
+        }),
+        Commands::Print { file_path, hex } => {
+            batch::run(input(file_path)?, jobs, out, |file_path, out| {
+                print_png(file_path, hex, out)
+            })
+        }
+        Commands::Validate { file_path } => batch::run(input(file_path)?, jobs, out, validate_png),
+        Commands::Repair {
+            file_path,
+            output,
+            backup,
         } => {
-            let result = remove(file_path, chunk_type);
-            match result {
-                Ok(_) => (),
-                Err(error) => panic!("Unable to remove chunk {}", error),
-            }
-        },
-        commands::Commands::Print { file_path } => {
-            print_png(file_path)?
-        },
+            let input = input(file_path)?;
+            check_single_output(&input, &output, "--output")?;
+            batch::run(input, jobs, out, |file_path, out| {
+                repair_png(file_path, output.clone(), backup.backup.clone(), out)
+            })
+        }
+        Commands::Frames { file_path } => batch::run(input(file_path)?, jobs, out, print_frames),
     }
+}
 
+/// Rejects an output file for a batch, as every file would be written to it.
+fn check_single_output(input: &Input, output: &Option<OsString>, flag: &str) -> Result<()> {
+    if input.is_batch() && output.is_some() {
+        return Err(PngError::InvalidArgument(format!(
+            "{} can't be used with several input files",
+            flag
+        )));
+    }
     Ok(())
 }
 
-fn print_png(file_path: Option<OsString>) -> Result<()> {
-    let png: Png;
-    match match_file(file_path).unwrap() {
-        (matched_png, _) => {
-            png = matched_png;
-        }
-    };
-
-    println!("{}", png);
+fn check_max_chunk_size(max_chunk_size: usize) -> Result<()> {
+    if max_chunk_size < message::MIN_CHUNK_SIZE {
+        return Err(PngError::InvalidArgument(format!(
+            "--max-chunk-size must be at least {} bytes, to hold a fragment's {} byte header \
+             and one byte of the message",
+            message::MIN_CHUNK_SIZE,
+            message::MIN_CHUNK_SIZE - 1
+        )));
+    }
     Ok(())
 }
 
-fn remove(file_path: Option<OsString>, chunk_type: String) -> Result<()> {
-    let mut png: Png;
-    let match_result = match_file(file_path).unwrap();
-    match match_result {
-        (matched_png, _) => {
-            png = matched_png;
+fn read_message(message: Option<String>, input_file: Option<OsString>) -> Result<Message> {
+    match (message, input_file) {
+        (_, Some(path)) if path == "-" => {
+            let mut data = Vec::new();
+            io::stdin().read_to_end(&mut data)?;
+            Ok(Message::Payload(Payload::new(data)))
         }
-    };
-
-    let removed_chunk = png.remove_chunk(&chunk_type);
-    match removed_chunk {
-        Ok(_) => {
-            println!("Removed message");
-            Ok(())
+        (_, Some(path)) => {
+            let data = fs::read(&path)?;
+            Ok(Message::Payload(Payload::from_file_data(path, data)))
         }
-        Err(error) => Err(error),
+        (Some(message), None) => Ok(Message::Text(message)),
+        (None, None) => Err(PngError::InvalidArgument(
+            "no message or input file given".to_string(),
+        )),
     }
 }
 
-fn decode(file_path: Option<OsString>, chunk_type: String) -> Result<()> {
-   let png: Png;
-    let match_result = match_file(file_path).unwrap();
-    match match_result {
-        (matched_png, _) => {
-            png = matched_png;
-        }
+fn validate_png(file_path: Option<OsString>, out: &Output) -> Result<()> {
+    let violations = read_only(file_path, out, |png| {
+        png.check_crcs()?;
+        Ok(png.validate())
+    })?;
+
+    if out.format() == Format::Json {
+        out.json(&ValidationReport::new(&violations))?;
+        return match violations.len() {
+            0 => Ok(()),
+            count => Err(PngError::ValidationFailed(count)),
+        };
     }
+    if violations.is_empty() {
+        out.status("PNG structure is valid")?;
+        return Ok(());
+    }
+    for violation in &violations {
+        out.result(violation)?;
+    }
+    Err(PngError::ValidationFailed(violations.len()))
+}
 
-    let matched_chunk = png.chunk_by_type(&chunk_type);
-    match matched_chunk {
-        Some(chunk) => {
-            let message = chunk.data_as_string().unwrap();
-            println!("Encoded Message \n\t{}", message);
+fn print_png(file_path: Option<OsString>, hex: bool, out: &Output) -> Result<()> {
+    read_only(file_path, out, |png| {
+        let report = PngReport::from_ref(png, hex);
+        match out.format() {
+            Format::Text => out.result(&report)?,
+            Format::Json => out.json(&report)?,
         }
-        None => println!("Unable to find matching chunk type"),
+        // The report lists every bad CRC, the first also sets the exit status
+        png.check_crcs()
+    })
+}
+
+fn repair_png(
+    file_path: Option<OsString>,
+    output: Option<OsString>,
+    backup: Option<OsString>,
+    out: &Output,
+) -> Result<()> {
+    let path = file_path.ok_or_else(invalid_filename)?;
+    let (mut png, diagnostics) = Png::from_file_with_options(&path, &ParseOptions::lenient())
+        .map_err(|error| with_path(error, &path))?;
+    out.detail(format_args!(
+        "Read {}: {} chunks",
+        path.to_string_lossy(),
+        png.chunks().len()
+    ));
+    png.add_missing_iend();
+    png.move_chunks_after_iend();
+    let renumbered = png.renumber_frames();
+
+    if diagnostics.is_empty() && !renumbered {
+        out.status("Nothing to repair")?;
+        return Ok(());
+    }
+    for diagnostic in &diagnostics {
+        out.status(format_args!("Repaired: {}", diagnostic))?;
     }
+    if renumbered {
+        out.status("Repaired: APNG sequence numbers and frame count")?;
+    }
+    write_png(
+        &png,
+        output.as_ref().unwrap_or(&path),
+        backup.as_deref(),
+        out,
+    )
+}
+
+fn print_frames(file_path: Option<OsString>, out: &Output) -> Result<()> {
+    let (png, _) = match_file(file_path, out)?;
 
+    let animation_control = match png.animation_control()? {
+        Some(animation_control) => animation_control,
+        None => {
+            out.result("Not an animated PNG")?;
+            return Ok(());
+        }
+    };
+    let plays = match animation_control.num_plays() {
+        0 => "forever".to_string(),
+        plays => format!("{} times", plays),
+    };
+    out.result(format_args!(
+        "{} frames, played {}",
+        animation_control.num_frames(),
+        plays
+    ))?;
+    for (number, frame) in png.frames()?.iter().enumerate() {
+        let control = frame.control();
+        let source = match frame.is_default_image() {
+            true => "IDAT",
+            false => "fdAT",
+        };
+        out.result(format_args!(
+            "Frame {}: {}x{} at ({}, {}), {:.3}s, dispose {}, blend {}, {} {} chunk(s)",
+            number,
+            control.width(),
+            control.height(),
+            control.x_offset(),
+            control.y_offset(),
+            control.delay(),
+            control.dispose_op(),
+            control.blend_op(),
+            frame.chunks().len(),
+            source
+        ))?;
+    }
     Ok(())
 }
 
-fn encode(
+fn remove(
     file_path: Option<OsString>,
     chunk_type: String,
-    message: String,
-    output_file: Option<OsString>,
+    keyword: Option<String>,
+    backup: Option<OsString>,
+    out: &Output,
 ) -> Result<()> {
-    let mut png: Png;
-    let matched_path: OsString;
-    let match_result = match_file(file_path).unwrap();
-    match match_result {
-        (matched_png, path) => {
-            png = matched_png;
-            matched_path = path;
+    let (mut png, path) = match_file(file_path, out)?;
+
+    match keyword {
+        Some(keyword) => {
+            let chunk_type = ChunkType::from_str(&chunk_type)?;
+            check_text_chunk_type(&chunk_type)?;
+            if png.remove_text(chunk_type, &keyword) == 0 {
+                return Err(PngError::ChunkNotFound(format!(
+                    "{} chunk \"{}\"",
+                    chunk_type, keyword
+                )));
+            }
         }
-    }
-    let chunk: Chunk;
-    let chunk_result = png.chunk_by_type(&chunk_type);
-    match chunk_result {
-        Some(_) => {
-            png.remove_chunk(&chunk_type)?;
+        None => {
+            // Removes every fragment of a split message as well
+            png.remove_chunks(&chunk_type)?;
         }
-        None => (),
     }
-    let chunk_type_object = ChunkType::from_str(&chunk_type).unwrap();
-    chunk = Chunk::new(chunk_type_object, message.into_bytes());
-    png.append_chunk(chunk);
+    write_png(&png, &path, backup.as_deref(), out)?;
+    out.status("Removed message")
+}
+
+fn decode(
+    file_path: Option<OsString>,
+    location: &Location,
+    secret: Option<Vec<u8>>,
+    output_file: Option<OsString>,
+    out: &Output,
+) -> Result<()> {
+    let message = read_only(file_path, out, |png| {
+        message::decode_message_ref(png, location, secret.as_deref())
+    })?;
 
     match output_file {
-        Some(path) => {
-            write_png(&png, &path);
-        }
+        Some(path) if path == "-" => io::stdout().write_all(&message.into_bytes())?,
+        Some(path) => fs::write(path, message.into_bytes())?,
         None => {
-            write_png(&png, &matched_path);
+            // Payloads that happen to be text are shown like any other message
+            let text = match &message {
+                Message::Text(text) => Ok(text.as_str()),
+                Message::Payload(payload) => {
+                    std::str::from_utf8(payload.data()).map_err(|_| payload)
+                }
+            };
+            match out.format() {
+                Format::Text => match text {
+                    Ok(message) => out.result(format_args!("Encoded Message \n\t{}", message))?,
+                    Err(payload) => out.result(format_args!(
+                        "Encoded Payload \n\t{} ({}, {} bytes), use --output-file to save it",
+                        payload.filename().unwrap_or("unnamed"),
+                        payload.mime_type(),
+                        payload.data().len()
+                    ))?,
+                },
+                Format::Json => {
+                    let (chunk_type, keyword) = match location {
+                        Location::Chunk(chunk_type) => (*chunk_type, None),
+                        Location::Text {
+                            chunk_type,
+                            keyword,
+                        } => (*chunk_type, Some(keyword.as_str())),
+                        Location::Pixels => (ChunkType::IDAT, None),
+                    };
+                    out.json(&MessageReport {
+                        chunk_type: chunk_type.as_str(),
+                        keyword,
+                        message: text.ok(),
+                        filename: text.err().and_then(Payload::filename),
+                        mime_type: text.err().map(Payload::mime_type),
+                    })?
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn encode(
+    file_path: Option<OsString>,
+    location: &Location,
+    message: Message,
+    output_file: Option<OsString>,
+    options: &EncodeOptions,
+    backup: Option<OsString>,
+    out: &Output,
+) -> Result<()> {
+    let (mut png, matched_path) = match_file(file_path, out)?;
+
+    if *location == Location::Pixels {
+        out.status(format_args!(
+            "LSB capacity: {} bytes",
+            lsb::capacity(&png.ihdr()?)?
+        ))?;
+    }
+    message::encode(&mut png, location, message, options)?;
+
+    write_png(
+        &png,
+        output_file.as_ref().unwrap_or(&matched_path),
+        backup.as_deref(),
+        out,
+    )
+}
+
+/// Where the command line arguments say the message is hidden.
+fn location(chunk_type: &str, keyword: Option<String>, method: Method) -> Result<Location> {
+    match (keyword, method) {
+        (Some(_), Method::Lsb) => Err(text_with_lsb()),
+        (Some(keyword), Method::Chunk) => {
+            let chunk_type = ChunkType::from_str(chunk_type)?;
+            check_text_chunk_type(&chunk_type)?;
+            Ok(Location::Text {
+                chunk_type,
+                keyword,
+            })
         }
+        (None, Method::Chunk) => Ok(Location::Chunk(ChunkType::from_str(chunk_type)?)),
+        (None, Method::Lsb) => Ok(Location::Pixels),
     }
+}
 
+fn check_text_chunk_type(chunk_type: &ChunkType) -> Result<()> {
+    if !is_text_chunk_type(chunk_type) {
+        return Err(PngError::InvalidArgument(format!(
+            "--keyword only applies to tEXt, zTXt and iTXt chunks, not {}",
+            chunk_type
+        )));
+    }
     Ok(())
 }
 
-fn match_file(file_path: Option<OsString>) -> Result<(Png, OsString)> {
+/// Text chunks are separate from the pixel data, so they can't be combined with LSB.
+fn text_with_lsb() -> PngError {
+    PngError::InvalidArgument("--keyword can't be used with --method lsb".to_string())
+}
+
+/// Clap requires FILE_PATH, so this only happens if a command is run without one.
+fn invalid_filename() -> PngError {
+    PngError::InvalidArgument("Invalid filename".to_string())
+}
+
+/// Reads the password or key file contents used to derive the encryption key.
+fn load_secret(args: SecretArgs) -> Result<Option<Vec<u8>>> {
+    match (args.password, args.key_file) {
+        (Some(password), _) => Ok(Some(password.into_bytes())),
+        (None, Some(key_file)) => Ok(Some(fs::read(key_file)?)),
+        (None, None) => Ok(None),
+    }
+}
+
+fn match_file(file_path: Option<OsString>, out: &Output) -> Result<(Png, OsString)> {
     match file_path {
         Some(path) => {
-            let png = {
-                let parse_result = Png::from_file(&path);
-                match parse_result {
-                    Ok(parsed) => parsed,
-                    Err(error) => panic!("Error parsing png {}", error),
-                }
-            };
-            return Ok((png, path));
-        }
-        None => {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "Invalid filename",
-            )));
+            let png = Png::from_file(&path).map_err(|error| with_path(error, &path))?;
+            out.detail(format_args!(
+                "Read {}: {} chunks",
+                path.to_string_lossy(),
+                png.chunks().len()
+            ));
+            Ok((png, path))
         }
+        None => Err(invalid_filename()),
     }
 }
 
-fn write_png(png: &Png, path: &OsString) {
-    let mut file = File::create(path).unwrap();
-    let bytes = png.as_bytes();
-    file.write_all(&bytes).unwrap();
+/// Runs `command` on the PNG at `file_path` without copying the file into memory. Only
+/// the parts of the file that `command` reads are loaded, as the file is memory-mapped
+/// when the `mmap` feature is enabled.
+fn read_only<T>(
+    file_path: Option<OsString>,
+    out: &Output,
+    command: impl FnOnce(&PngRef) -> Result<T>,
+) -> Result<T> {
+    let path = file_path.ok_or_else(invalid_filename)?;
+    let bytes = load_file(&path).map_err(|error| with_path(error, &path))?;
+    let png = PngRef::try_from(&bytes[..])?;
+    out.detail(format_args!(
+        "Read {}: {} chunks",
+        path.to_string_lossy(),
+        png.chunks().len()
+    ));
+    command(&png)
+}
+
+#[cfg(feature = "mmap")]
+fn load_file(path: &OsStr) -> Result<MappedFile> {
+    MappedFile::open(path)
+}
+
+#[cfg(not(feature = "mmap"))]
+fn load_file(path: &OsStr) -> Result<Vec<u8>> {
+    Ok(fs::read(path)?)
+}
+
+/// Writes `png` to `path` atomically, so a failed write never destroys the file it replaces.
+fn write_png(png: &Png, path: &OsString, backup: Option<&OsStr>, out: &Output) -> Result<()> {
+    let path = Path::new(path);
+    let backup = atomic::write_atomic(path, backup, |writer| png.write_with_trailing_to(writer))
+        .map_err(|error| with_path(error, path.as_os_str()))?;
+    out.detail(format_args!("Wrote {}", path.display()));
+    if let Some(backup) = backup {
+        out.detail(format_args!("Kept the original as {}", backup.display()));
+    }
+    Ok(())
+}
+
+/// Names the file in I/O errors, which otherwise don't say which file they're about.
+fn with_path(error: PngError, path: &OsStr) -> PngError {
+    match error {
+        PngError::Io(error) => PngError::Io(io::Error::new(
+            error.kind(),
+            format!("{}: {}", path.to_string_lossy(), error),
+        )),
+        error => error,
+    }
 }
@@ -0,0 +1,87 @@
+use std::convert::TryFrom;
+use std::fs::File;
+use std::ops::Deref;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::error::Result;
+use crate::png_ref::PngRef;
+
+/// A file mapped read-only into memory, for parsing with `PngRef` without reading the
+/// whole file.
+///
+/// Pages are only read from disk when they're first accessed, so looking up a chunk by
+/// type reads the chunk headers but not the image data in between.
+///
+/// The contents are undefined if the file is modified while mapped, as with any memory
+/// map, so only map files that no other process is writing to.
+#[derive(Debug)]
+pub struct MappedFile {
+    map: Mmap,
+}
+
+impl MappedFile {
+    /// Maps the file at `path` into memory.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<MappedFile> {
+        let file = File::open(path)?;
+        // SAFETY: the map is read-only, and the caveat about the file changing underneath
+        // it is documented on `MappedFile`.
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(MappedFile { map })
+    }
+
+    /// Parses the mapped file as a PNG.
+    pub fn png_ref(&self) -> Result<PngRef<'_>> {
+        PngRef::try_from(&self.map[..])
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use crate::error::PngError;
+    use crate::png::Png;
+    use std::fs;
+
+    #[test]
+    fn test_maps_png() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::tEXt, b"Title\0Dice".to_vec()),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ]);
+        fs::write(&path, png.as_bytes()).unwrap();
+
+        let mapped = MappedFile::open(&path).unwrap();
+        assert_eq!(&mapped[..], png.as_bytes());
+        let png_ref = mapped.png_ref().unwrap();
+        assert!(png_ref.check_crcs().is_ok());
+        assert_eq!(png_ref.chunks().len(), 2);
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty.png");
+        fs::write(&path, b"").unwrap();
+
+        let mapped = MappedFile::open(&path).unwrap();
+        assert!(mapped.is_empty());
+        assert!(matches!(
+            mapped.png_ref(),
+            Err(PngError::InvalidSignature(_))
+        ));
+    }
+}
@@ -0,0 +1,568 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+#[cfg(feature = "crypto")]
+use crate::crypto;
+use crate::error::{PngError, Result};
+use crate::fragment::{self, MAX_CHUNK_LENGTH};
+use crate::idat::IdatOptions;
+use crate::lsb;
+use crate::payload::Payload;
+use crate::png::{ChunkPosition, Png};
+use crate::png_ref::PngRef;
+use crate::text::{is_text_chunk_type, text_chunk};
+
+/// A message to hide in a PNG.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// Text, stored as UTF-8 bytes
+    Text(String),
+    /// Binary data, stored in a payload envelope that keeps its file name and MIME type
+    Payload(Payload),
+}
+
+impl Message {
+    /// The text as UTF-8 bytes, or the payload's data
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Message::Text(text) => text.into_bytes(),
+            Message::Payload(payload) => payload.into_data(),
+        }
+    }
+
+    /// Text if `data` is valid UTF-8, otherwise an anonymous payload.
+    fn from_bytes(data: Vec<u8>) -> Message {
+        match String::from_utf8(data) {
+            Ok(text) => Message::Text(text),
+            Err(error) => Message::Payload(Payload::new(error.into_bytes())),
+        }
+    }
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Self {
+        Message::Text(text)
+    }
+}
+
+impl From<Payload> for Message {
+    fn from(payload: Payload) -> Self {
+        Message::Payload(payload)
+    }
+}
+
+/// Where a message is hidden in a PNG.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    /// In chunks of their own type, split into fragments if the message is too large for
+    /// one chunk
+    Chunk(ChunkType),
+    /// In a tEXt, zTXt or iTXt chunk under `keyword`. Only plain text can be stored here
+    Text {
+        chunk_type: ChunkType,
+        keyword: String,
+    },
+    /// In the least significant bits of the pixel samples, see `lsb`
+    Pixels,
+}
+
+impl Location {
+    /// Whether a message at this location may be stored in chunks of `chunk_type`.
+    pub fn reads(&self, chunk_type: &ChunkType) -> bool {
+        match self {
+            Location::Chunk(message_type) => message_type == chunk_type,
+            Location::Text { .. } => is_text_chunk_type(chunk_type),
+            Location::Pixels => true,
+        }
+    }
+}
+
+// The first byte of every message written by `seal` says how to read the rest, so a
+// message is never recognized by its contents. Chunks holding a fragment of a split
+// message are tagged the same way.
+const KIND_TEXT: u8 = 0;
+const KIND_PAYLOAD: u8 = 1;
+const KIND_ENCRYPTED: u8 = 2;
+const KIND_FRAGMENT: u8 = 3;
+
+/// The smallest usable maximum chunk size: a fragment needs room for its kind tag, the
+/// fragment header and at least one byte of the message.
+pub const MIN_CHUNK_SIZE: usize = 1 + fragment::HEADER_LENGTH + 1;
+
+/// Options for `encode`. The default places the message before IEND in a single chunk,
+/// without encryption.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodeOptions {
+    position: ChunkPosition,
+    max_chunk_size: usize,
+    secret: Option<Vec<u8>>,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions {
+            position: ChunkPosition::BeforeIend,
+            max_chunk_size: MAX_CHUNK_LENGTH,
+            secret: None,
+        }
+    }
+}
+
+impl EncodeOptions {
+    /// Where the message chunks go. Ignored for `Location::Pixels`
+    pub fn with_position(mut self, position: ChunkPosition) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Splits messages larger than this across several chunks. Clamped to
+    /// `MIN_CHUNK_SIZE..=MAX_CHUNK_LENGTH`, and only used for `Location::Chunk`
+    pub fn with_max_chunk_size(mut self, max_chunk_size: usize) -> Self {
+        self.max_chunk_size = max_chunk_size.clamp(MIN_CHUNK_SIZE, MAX_CHUNK_LENGTH);
+        self
+    }
+
+    /// Encrypts the message with a key derived from `secret`, a password or the contents
+    /// of a key file. Needs the `crypto` feature
+    pub fn with_secret(mut self, secret: Vec<u8>) -> Self {
+        self.secret = Some(secret);
+        self
+    }
+
+    /// Where the message chunks go
+    pub fn position(&self) -> ChunkPosition {
+        self.position
+    }
+
+    /// The largest chunk a message is stored in before it is split
+    pub fn max_chunk_size(&self) -> usize {
+        self.max_chunk_size
+    }
+
+    /// The password or key the message is encrypted with
+    pub fn secret(&self) -> Option<&[u8]> {
+        self.secret.as_deref()
+    }
+}
+
+/// Hides `message` in `png` at `location`, replacing any message already stored there.
+pub fn encode(
+    png: &mut Png,
+    location: &Location,
+    message: Message,
+    options: &EncodeOptions,
+) -> Result<()> {
+    match location {
+        Location::Text {
+            chunk_type,
+            keyword,
+        } => {
+            let text = match (message, &options.secret) {
+                (Message::Text(text), None) => text,
+                (Message::Payload(_), _) => return Err(binary_in_text_chunk("binary payloads")),
+                (_, Some(_)) => return Err(binary_in_text_chunk("encrypted messages")),
+            };
+            let chunk = text_chunk(*chunk_type, keyword, &text)?;
+            png.remove_text(*chunk_type, keyword);
+            png.insert_chunk(options.position, chunk)
+        }
+        Location::Chunk(chunk_type) => {
+            check_not_text_chunk_type(*chunk_type)?;
+            let data = seal(message, options.secret())?;
+            let chunks = if data.len() <= options.max_chunk_size {
+                vec![Chunk::new(*chunk_type, data)]
+            } else {
+                fragment::split(&data, options.max_chunk_size - 1)?
+                    .into_iter()
+                    .map(|fragment| Chunk::new(*chunk_type, tagged(KIND_FRAGMENT, &fragment)))
+                    .collect()
+            };
+            // Replace the previous message, including all of its fragments
+            let _ = png.remove_chunks(chunk_type.as_str());
+            png.insert_chunks(options.position, chunks)
+        }
+        Location::Pixels => {
+            let data = seal(message, options.secret())?;
+            let ihdr = png.ihdr()?;
+            let mut pixels = png.decode_pixels()?;
+            lsb::embed(&ihdr, pixels.data_mut(), &data)?;
+            png.encode_pixels(&pixels, &IdatOptions::default())
+        }
+    }
+}
+
+/// Recovers the message hidden at `location` by `encode`, returning the text as UTF-8
+/// bytes or the payload's data. `secret` must be given if the message is encrypted.
+pub fn decode(png: &Png, location: &Location, secret: Option<&[u8]>) -> Result<Vec<u8>> {
+    decode_message(png, location, secret).map(Message::into_bytes)
+}
+
+/// Like `decode`, but returns the `Message` itself so that a payload keeps its file name
+/// and MIME type.
+pub fn decode_message(png: &Png, location: &Location, secret: Option<&[u8]>) -> Result<Message> {
+    match location {
+        Location::Text {
+            chunk_type,
+            keyword,
+        } => {
+            if !is_text_chunk_type(chunk_type) {
+                return Err(PngError::InvalidText(format!(
+                    "{} is not a text chunk type",
+                    chunk_type
+                )));
+            }
+            if secret.is_some() {
+                return Err(binary_in_text_chunk("encrypted messages"));
+            }
+            Ok(Message::Text(
+                png.text_by_type_and_keyword(*chunk_type, keyword)?,
+            ))
+        }
+        Location::Chunk(chunk_type) => {
+            let chunks = png.chunks_by_type(chunk_type.as_str());
+            let data = match chunks.first() {
+                None => return Err(PngError::ChunkNotFound(chunk_type.to_string())),
+                Some(chunk) if chunk.data().first() == Some(&KIND_FRAGMENT) => {
+                    fragment::reassemble(chunks.iter().map(|chunk| {
+                        let data = chunk.data();
+                        data.strip_prefix(&[KIND_FRAGMENT]).unwrap_or(data)
+                    }))?
+                }
+                Some(chunk) => chunk.data().to_vec(),
+            };
+            open_message(data, secret)
+        }
+        Location::Pixels => {
+            let data = lsb::extract(&png.ihdr()?, png.decode_pixels()?.data())?;
+            open_message(data, secret)
+        }
+    }
+}
+
+/// Like `decode`, but for a borrowed PNG. Only the chunks that `location` reads are copied
+/// and have their CRCs checked, so the image data isn't touched unless the message is
+/// hidden in the pixels.
+pub fn decode_ref(png: &PngRef, location: &Location, secret: Option<&[u8]>) -> Result<Vec<u8>> {
+    decode_message_ref(png, location, secret).map(Message::into_bytes)
+}
+
+/// Like `decode_message`, but for a borrowed PNG, see `decode_ref`.
+pub fn decode_message_ref(
+    png: &PngRef,
+    location: &Location,
+    secret: Option<&[u8]>,
+) -> Result<Message> {
+    let mut chunks = Vec::new();
+    for chunk in png.chunks() {
+        if location.reads(chunk.chunk_type()) {
+            chunk.check_crc()?;
+            chunks.push(chunk.to_chunk());
+        }
+    }
+    decode_message(&Png::from_chunks(chunks), location, secret)
+}
+
+/// Turns a message into the bytes to hide: a byte saying whether the rest is text or a
+/// payload envelope, all of which is encrypted and tagged again when a secret is given.
+pub fn seal(message: Message, secret: Option<&[u8]>) -> Result<Vec<u8>> {
+    let data = match message {
+        Message::Text(text) => tagged(KIND_TEXT, text.as_bytes()),
+        Message::Payload(payload) => tagged(KIND_PAYLOAD, &payload.as_bytes()),
+    };
+    match secret {
+        Some(secret) => Ok(tagged(KIND_ENCRYPTED, &encrypt(&data, secret)?)),
+        None => Ok(data),
+    }
+}
+
+/// Reverses `seal`, returning the bytes of the original text or payload data. Data
+/// without a known first byte was stored as plain text by older versions and is returned
+/// as it is.
+pub fn open(data: Vec<u8>, secret: Option<&[u8]>) -> Result<Vec<u8>> {
+    open_message(data, secret).map(Message::into_bytes)
+}
+
+/// Like `open`, but returns the `Message` itself. Text that isn't valid UTF-8 comes back
+/// as an anonymous payload.
+pub fn open_message(data: Vec<u8>, secret: Option<&[u8]>) -> Result<Message> {
+    match (data.first().copied(), secret) {
+        (Some(KIND_ENCRYPTED), Some(secret)) => match decrypt(&data[1..], secret)? {
+            data if data.first() == Some(&KIND_ENCRYPTED) => Err(PngError::InvalidEnvelope(
+                "encrypted message is encrypted again".to_string(),
+            )),
+            data => open_message(data, None),
+        },
+        (Some(KIND_ENCRYPTED), None) => Err(PngError::MissingSecret),
+        (_, Some(_)) => Err(PngError::InvalidEnvelope(
+            "data is not an encrypted message".to_string(),
+        )),
+        (Some(KIND_TEXT), None) => Ok(Message::from_bytes(data[1..].to_vec())),
+        (Some(KIND_PAYLOAD), None) => Ok(Message::Payload(Payload::try_from(&data[1..])?)),
+        (Some(KIND_FRAGMENT), None) => Err(PngError::InvalidFragment(
+            "a fragment can't be read without the rest of the message".to_string(),
+        )),
+        _ => Ok(Message::from_bytes(data)),
+    }
+}
+
+fn tagged(kind: u8, data: &[u8]) -> Vec<u8> {
+    let mut tagged = Vec::with_capacity(1 + data.len());
+    tagged.push(kind);
+    tagged.extend_from_slice(data);
+    tagged
+}
+
+#[cfg(feature = "crypto")]
+fn encrypt(data: &[u8], secret: &[u8]) -> Result<Vec<u8>> {
+    crypto::encrypt(data, secret)
+}
+
+#[cfg(not(feature = "crypto"))]
+fn encrypt(_data: &[u8], _secret: &[u8]) -> Result<Vec<u8>> {
+    Err(PngError::FeatureDisabled("crypto"))
+}
+
+#[cfg(feature = "crypto")]
+fn decrypt(data: &[u8], secret: &[u8]) -> Result<Vec<u8>> {
+    crypto::decrypt(data, secret)
+}
+
+#[cfg(not(feature = "crypto"))]
+fn decrypt(_data: &[u8], _secret: &[u8]) -> Result<Vec<u8>> {
+    Err(PngError::FeatureDisabled("crypto"))
+}
+
+fn check_not_text_chunk_type(chunk_type: ChunkType) -> Result<()> {
+    if is_text_chunk_type(&chunk_type) {
+        return Err(PngError::InvalidText(format!(
+            "{} chunks need a keyword to store the message under",
+            chunk_type
+        )));
+    }
+    Ok(())
+}
+
+/// Text chunks only hold plain text, so binary data such as `what` is rejected.
+fn binary_in_text_chunk(what: &str) -> PngError {
+    PngError::InvalidText(format!("{} can't be stored in text chunks", what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idat;
+    use crate::ihdr::{ColorType, Ihdr, InterlaceMethod};
+    use crate::pixels::Pixels;
+    use std::str::FromStr;
+
+    fn png() -> Png {
+        let ihdr = Ihdr::new(16, 16, 8, ColorType::Rgb, InterlaceMethod::None).unwrap();
+        let data = (0..ihdr.stride() * 16).map(|n| n as u8).collect();
+        let pixels = Pixels::new(16, 16, ihdr.pixel_format(), data).unwrap();
+        let mut chunks = vec![ihdr.to_chunk()];
+        chunks.extend(idat::encode(&pixels, &IdatOptions::default()));
+        chunks.push(Chunk::new(ChunkType::IEND, vec![]));
+        Png::from_chunks(chunks)
+    }
+
+    fn rust_chunk() -> Location {
+        Location::Chunk(ChunkType::from_str("ruSt").unwrap())
+    }
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let mut png = png();
+        let options = EncodeOptions::default().with_max_chunk_size(16);
+        let message = "a message split across several chunks".to_string();
+        encode(&mut png, &rust_chunk(), message.clone().into(), &options).unwrap();
+        assert!(png.chunks_by_type("ruSt").len() > 1);
+        assert_eq!(
+            decode(&png, &rust_chunk(), None).unwrap(),
+            message.as_bytes()
+        );
+
+        encode(
+            &mut png,
+            &rust_chunk(),
+            "short".to_string().into(),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(png.chunks_by_type("ruSt").len(), 1);
+        assert_eq!(decode(&png, &rust_chunk(), None).unwrap(), b"short");
+    }
+
+    #[test]
+    fn test_smallest_chunk_size() {
+        let mut png = png();
+        let options = EncodeOptions::default().with_max_chunk_size(1);
+        assert_eq!(options.max_chunk_size(), MIN_CHUNK_SIZE);
+        let message = "split into single bytes".to_string();
+        encode(&mut png, &rust_chunk(), message.clone().into(), &options).unwrap();
+        let chunks = png.chunks_by_type("ruSt");
+        assert_eq!(chunks.len(), message.len() + 1);
+        assert!(chunks
+            .iter()
+            .all(|chunk| chunk.data().len() == MIN_CHUNK_SIZE));
+        assert_eq!(
+            decode(&png, &rust_chunk(), None).unwrap(),
+            message.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        let mut png = png();
+        let location = Location::Text {
+            chunk_type: ChunkType::zTXt,
+            keyword: "Comment".to_string(),
+        };
+        let options = EncodeOptions::default();
+        encode(&mut png, &location, "hello".to_string().into(), &options).unwrap();
+        assert_eq!(decode(&png, &location, None).unwrap(), b"hello");
+        let other_type = Location::Text {
+            chunk_type: ChunkType::tEXt,
+            keyword: "Comment".to_string(),
+        };
+        assert!(matches!(
+            decode(&png, &other_type, None),
+            Err(PngError::ChunkNotFound(_))
+        ));
+
+        let payload = Payload::new(vec![0, 1, 2]).into();
+        assert!(matches!(
+            encode(&mut png, &location, payload, &options),
+            Err(PngError::InvalidText(_))
+        ));
+    }
+
+    #[test]
+    fn test_pixels_round_trip() {
+        let mut png = png();
+        let payload = Payload::new(vec![0xde, 0xad, 0xbe, 0xef]);
+        encode(
+            &mut png,
+            &Location::Pixels,
+            payload.into(),
+            &EncodeOptions::default(),
+        )
+        .unwrap();
+        assert!(png.chunks_by_type("ruSt").is_empty());
+        assert_eq!(
+            decode(&png, &Location::Pixels, None).unwrap(),
+            [0xde, 0xad, 0xbe, 0xef]
+        );
+    }
+
+    #[test]
+    fn test_decode_message_keeps_payload_metadata() {
+        let mut png = png();
+        let payload = Payload::from_file_data("key.pem", vec![0xff, 0xfe]);
+        let options = EncodeOptions::default();
+        encode(&mut png, &rust_chunk(), payload.clone().into(), &options).unwrap();
+        assert_eq!(
+            decode_message(&png, &rust_chunk(), None).unwrap(),
+            Message::Payload(payload)
+        );
+
+        let mut png = self::png();
+        png.append_chunk(Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![0xff]));
+        assert_eq!(
+            decode_message(&png, &rust_chunk(), None).unwrap(),
+            Message::Payload(Payload::new(vec![0xff]))
+        );
+    }
+
+    #[test]
+    fn test_text_chunk_type_needs_keyword() {
+        let mut png = png();
+        assert!(matches!(
+            encode(
+                &mut png,
+                &Location::Chunk(ChunkType::tEXt),
+                "hi".to_string().into(),
+                &EncodeOptions::default()
+            ),
+            Err(PngError::InvalidText(_))
+        ));
+    }
+
+    #[test]
+    fn test_decode_ref_copies_only_message_chunks() {
+        let mut png = png();
+        let options = EncodeOptions::default().with_max_chunk_size(16);
+        encode(
+            &mut png,
+            &rust_chunk(),
+            "a message in several borrowed chunks".to_string().into(),
+            &options,
+        )
+        .unwrap();
+        let mut bytes = png.as_bytes();
+        // Corrupt the image data, which decoding a chunk message must not look at
+        let idat = png.chunk_offsets()[1] as usize + 8;
+        bytes[idat] ^= 0xff;
+
+        let png_ref = PngRef::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(
+            decode_ref(&png_ref, &rust_chunk(), None).unwrap(),
+            b"a message in several borrowed chunks"
+        );
+        assert!(matches!(
+            decode_ref(&png_ref, &Location::Pixels, None),
+            Err(PngError::CrcMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_text_like_a_tag_round_trip() {
+        for text in ["\u{1}payload", "\u{2}", "\u{3}fragment and more"] {
+            for location in [rust_chunk(), Location::Pixels] {
+                let mut png = png();
+                let options = EncodeOptions::default();
+                encode(&mut png, &location, text.to_string().into(), &options).unwrap();
+                assert_eq!(decode(&png, &location, None).unwrap(), text.as_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn test_untagged_text() {
+        let mut png = png();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        png.append_chunk(Chunk::new(chunk_type, b"from an older version".to_vec()));
+        assert_eq!(
+            decode(&png, &rust_chunk(), None).unwrap(),
+            b"from an older version"
+        );
+    }
+
+    #[test]
+    fn test_missing_message() {
+        assert!(matches!(
+            decode(&png(), &rust_chunk(), None),
+            Err(PngError::ChunkNotFound(_))
+        ));
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn test_encrypted_round_trip() {
+        let mut png = png();
+        let options = EncodeOptions::default().with_secret(b"hunter2".to_vec());
+        encode(
+            &mut png,
+            &rust_chunk(),
+            "secret".to_string().into(),
+            &options,
+        )
+        .unwrap();
+        assert!(matches!(
+            decode(&png, &rust_chunk(), None),
+            Err(PngError::MissingSecret)
+        ));
+        assert_eq!(
+            decode(&png, &rust_chunk(), Some(b"hunter2")).unwrap(),
+            b"secret"
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use serde::Serialize;
+
+use crate::commands::Format;
+use pngme::{PngError, Result};
+
+/// Exit code for failures without a more specific code below.
+pub const EXIT_FAILURE: u8 = 1;
+/// Exit code for invalid command line usage, the same code clap exits with.
+pub const EXIT_USAGE: u8 = 2;
+/// Exit code for a chunk, keyword or text that doesn't exist in the file.
+pub const EXIT_NOT_FOUND: u8 = 3;
+/// Exit code for a file that can't be parsed as a PNG or that fails validation.
+pub const EXIT_INVALID_PNG: u8 = 4;
+/// Exit code for a missing, wrong or rejected password or key.
+pub const EXIT_AUTHENTICATION: u8 = 5;
+/// Exit code for a file that can't be read or written.
+pub const EXIT_IO: u8 = 6;
+
+/// The exit codes as listed in `pngme --help`.
+pub const EXIT_CODES_HELP: &str = "\
+Exit status:
+  0  Success
+  1  Any other failure, such as a message that doesn't fit or some files of a batch
+     failing
+  2  Invalid command line usage
+  3  The chunk, keyword or text was not found
+  4  The file is not a valid PNG, or fails validation
+  5  The password or key is missing or wrong
+  6  A file could not be read or written";
+
+/// The process exit code for `error`.
+pub fn exit_code(error: &PngError) -> u8 {
+    match error {
+        PngError::InvalidArgument(_) => EXIT_USAGE,
+        PngError::ChunkNotFound(_) => EXIT_NOT_FOUND,
+        PngError::InvalidSignature(_)
+        | PngError::CrcMismatch { .. }
+        | PngError::TruncatedChunk { .. }
+        | PngError::InvalidChunkType(_)
+        | PngError::ChunkAfterIend(_)
+        | PngError::InvalidIhdr(_)
+        | PngError::InvalidTime(_)
+        | PngError::InvalidApng(_)
+        | PngError::InvalidImageData(_)
+        | PngError::ValidationFailed(_) => EXIT_INVALID_PNG,
+        PngError::MissingSecret | PngError::AuthenticationFailed => EXIT_AUTHENTICATION,
+        PngError::Io(_) => EXIT_IO,
+        _ => EXIT_FAILURE,
+    }
+}
+
+/// How much the CLI reports besides the results it was asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    /// Results and errors only
+    Quiet,
+    /// Also confirmations and progress
+    #[default]
+    Normal,
+    /// Also the files read and written, and the causes of errors
+    Verbose,
+}
+
+/// Writes CLI output: results and status messages to stdout, details and errors to
+/// stderr, filtered by `Verbosity`.
+#[derive(Debug)]
+pub struct Output {
+    verbosity: Verbosity,
+    format: Format,
+    /// Collects what would go to stdout and stderr, see `buffered`
+    buffers: Option<Buffers>,
+}
+
+/// The output of a `buffered` `Output`.
+#[derive(Debug, Default)]
+struct Buffers {
+    stdout: Mutex<Vec<u8>>,
+    stderr: Mutex<Vec<u8>>,
+}
+
+impl Output {
+    pub fn new(verbosity: Verbosity, format: Format) -> Output {
+        Output {
+            verbosity,
+            format,
+            buffers: None,
+        }
+    }
+
+    /// An `Output` with the same settings that keeps what would go to stdout and stderr
+    /// in memory, so that files processed in parallel don't mix their output.
+    pub fn buffered(&self) -> Output {
+        Output {
+            buffers: Some(Buffers::default()),
+            ..Output::new(self.verbosity, self.format)
+        }
+    }
+
+    /// What was written to stdout and stderr of a `buffered` output.
+    pub fn into_buffers(self) -> (Vec<u8>, Vec<u8>) {
+        let into_inner = |buffer: Mutex<Vec<u8>>| {
+            buffer
+                .into_inner()
+                .unwrap_or_else(|error| error.into_inner())
+        };
+        self.buffers
+            .map(|buffers| (into_inner(buffers.stdout), into_inner(buffers.stderr)))
+            .unwrap_or_default()
+    }
+
+    /// Runs `write` on stdout, or on the stdout buffer of a `buffered` output.
+    fn stdout(&self, write: impl FnOnce(&mut dyn Write) -> io::Result<()>) -> Result<()> {
+        match &self.buffers {
+            Some(buffers) => write(&mut *lock(&buffers.stdout))?,
+            None => write(&mut io::stdout().lock())?,
+        }
+        Ok(())
+    }
+
+    /// Runs `write` on stderr, or on the stderr buffer of a `buffered` output. Failing to
+    /// report something on stderr is ignored, as there is nowhere left to report it.
+    fn stderr(&self, write: impl FnOnce(&mut dyn Write) -> io::Result<()>) {
+        let _ = match &self.buffers {
+            Some(buffers) => write(&mut *lock(&buffers.stderr)),
+            None => write(&mut io::stderr().lock()),
+        };
+    }
+
+    /// The format that results are written in
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Writes a result the command was run for, which is shown even with `--quiet`.
+    pub fn result(&self, result: impl Display) -> Result<()> {
+        self.stdout(|stdout| writeln!(stdout, "{}", result))
+    }
+
+    /// Writes bytes that were collected on stdout by a `buffered` output.
+    pub fn raw(&self, bytes: &[u8]) -> Result<()> {
+        self.stdout(|stdout| stdout.write_all(bytes))
+    }
+
+    /// Writes bytes that were collected on stderr by a `buffered` output.
+    pub fn raw_error(&self, bytes: &[u8]) {
+        self.stderr(|stderr| stderr.write_all(bytes))
+    }
+
+    /// Writes `value` as a pretty printed JSON result.
+    pub fn json<T: Serialize>(&self, value: &T) -> Result<()> {
+        self.stdout(|stdout| {
+            serde_json::to_writer_pretty(&mut *stdout, value).map_err(io::Error::from)?;
+            writeln!(stdout)
+        })
+    }
+
+    /// Writes a confirmation or progress message, unless `--quiet` was given.
+    pub fn status(&self, message: impl Display) -> Result<()> {
+        if self.verbosity >= Verbosity::Normal {
+            self.result(message)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a message to stderr if `--verbose` was given.
+    pub fn detail(&self, message: impl Display) {
+        if self.verbosity >= Verbosity::Verbose {
+            self.stderr(|stderr| writeln!(stderr, "{}", message));
+        }
+    }
+
+    /// Reports `error` on stderr, followed by its causes if `--verbose` was given.
+    pub fn error(&self, error: &PngError) {
+        self.stderr(|stderr| writeln!(stderr, "error: {}", error));
+        self.causes(error);
+    }
+
+    /// Reports that processing the file at `path` failed with `error`.
+    pub fn file_error(&self, path: &Path, error: &PngError) {
+        self.stderr(|stderr| writeln!(stderr, "error: {}: {}", path.display(), error));
+        self.causes(error);
+    }
+
+    /// Lists the causes of `error` if `--verbose` was given.
+    fn causes(&self, error: &PngError) {
+        if self.verbosity >= Verbosity::Verbose {
+            let mut source = error.source();
+            while let Some(cause) = source {
+                self.stderr(|stderr| writeln!(stderr, "  caused by: {}", cause));
+                source = cause.source();
+            }
+        }
+    }
+}
+
+fn lock(buffer: &Mutex<Vec<u8>>) -> MutexGuard<'_, Vec<u8>> {
+    buffer.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme::ChunkType;
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(
+            exit_code(&PngError::InvalidArgument("no message".to_string())),
+            EXIT_USAGE
+        );
+        assert_eq!(
+            exit_code(&PngError::ChunkNotFound("ruSt".to_string())),
+            EXIT_NOT_FOUND
+        );
+        assert_eq!(
+            exit_code(&PngError::InvalidSignature([0; 8])),
+            EXIT_INVALID_PNG
+        );
+        assert_eq!(
+            exit_code(&PngError::CrcMismatch {
+                chunk_type: ChunkType::IEND,
+                offset: 8,
+                expected: 0,
+                actual: 1
+            }),
+            EXIT_INVALID_PNG
+        );
+        assert_eq!(exit_code(&PngError::ValidationFailed(2)), EXIT_INVALID_PNG);
+        assert_eq!(
+            exit_code(&PngError::AuthenticationFailed),
+            EXIT_AUTHENTICATION
+        );
+        assert_eq!(exit_code(&PngError::MissingSecret), EXIT_AUTHENTICATION);
+        let io_error = io::Error::new(io::ErrorKind::NotFound, "missing");
+        assert_eq!(exit_code(&PngError::Io(io_error)), EXIT_IO);
+        assert_eq!(
+            exit_code(&PngError::MessageTooLarge {
+                size: 2,
+                capacity: 1
+            }),
+            EXIT_FAILURE
+        );
+    }
+
+    #[test]
+    fn test_buffered() {
+        let out = Output::new(Verbosity::Quiet, Format::Text).buffered();
+        out.result("result").unwrap();
+        out.status("hidden by --quiet").unwrap();
+        out.raw(b"raw\n").unwrap();
+        out.detail("hidden without --verbose");
+        out.error(&PngError::MissingSecret);
+        assert_eq!(
+            out.into_buffers(),
+            (
+                b"result\nraw\n".to_vec(),
+                b"error: message is encrypted but no password or key was given\n".to_vec()
+            )
+        );
+    }
+
+    #[test]
+    fn test_verbosity_order() {
+        assert!(Verbosity::Quiet < Verbosity::Normal);
+        assert!(Verbosity::Normal < Verbosity::Verbose);
+        assert_eq!(Verbosity::default(), Verbosity::Normal);
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt;
+
+use crate::chunk_type::ChunkType;
+
+/// What to do when a chunk's stored CRC doesn't match its contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrcPolicy {
+    /// Fail with `PngError::CrcMismatch`
+    #[default]
+    Strict,
+    /// Keep the chunk and report a `Diagnostic::CrcMismatch`
+    Warn,
+    /// Keep the chunk without reporting anything
+    Ignore,
+}
+
+/// Options for reading damaged PNGs. The default is as strict as `Png::from_reader`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseOptions {
+    /// How chunks with a bad CRC are handled
+    pub crc_policy: CrcPolicy,
+    /// Drop a truncated chunk at the end of the stream, reporting a
+    /// `Diagnostic::TruncatedChunk`, instead of failing
+    pub allow_truncated: bool,
+}
+
+impl ParseOptions {
+    /// Options that read as much of a damaged file as possible, reporting every problem.
+    pub fn lenient() -> ParseOptions {
+        ParseOptions {
+            crc_policy: CrcPolicy::Warn,
+            allow_truncated: true,
+        }
+    }
+}
+
+/// A problem found while reading a PNG that didn't stop it from being read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// The chunk at `offset` was kept even though its stored CRC is wrong.
+    CrcMismatch {
+        chunk_type: ChunkType,
+        offset: u64,
+        stored: u32,
+        computed: u32,
+    },
+    /// The stream ended partway through the chunk at `offset`, which was dropped.
+    TruncatedChunk { offset: u64 },
+    /// The stream ended without an IEND chunk.
+    MissingIend,
+    /// This many chunks follow IEND, which must be the last chunk.
+    ChunksAfterIend { count: usize },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::CrcMismatch {
+                chunk_type,
+                offset,
+                stored,
+                computed,
+            } => write!(
+                f,
+                "CRC mismatch in {} chunk at offset {}: stored {:#010x}, computed {:#010x}",
+                chunk_type, offset, stored, computed
+            ),
+            Diagnostic::TruncatedChunk { offset } => {
+                write!(f, "truncated chunk at offset {} was dropped", offset)
+            }
+            Diagnostic::MissingIend => write!(f, "IEND chunk is missing"),
+            Diagnostic::ChunksAfterIend { count: 1 } => write!(f, "1 chunk after IEND"),
+            Diagnostic::ChunksAfterIend { count } => write!(f, "{} chunks after IEND", count),
+        }
+    }
+}
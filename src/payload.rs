@@ -0,0 +1,217 @@
+use std::convert::TryFrom;
+use std::path::Path;
+
+use crate::error::{PngError, Result};
+
+const VERSION: u8 = 1;
+
+/// An arbitrary binary payload with the metadata needed to restore it as a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payload {
+    filename: Option<String>,
+    mime_type: String,
+    data: Vec<u8>,
+}
+
+impl Payload {
+    /// The MIME type used when nothing better is known about the data.
+    pub const DEFAULT_MIME_TYPE: &'static str = "application/octet-stream";
+
+    /// Creates an anonymous `Payload` with the default MIME type.
+    pub fn new(data: Vec<u8>) -> Payload {
+        Payload {
+            filename: None,
+            mime_type: Payload::DEFAULT_MIME_TYPE.to_string(),
+            data,
+        }
+    }
+
+    /// Creates a `Payload` named after the final component of `path`, guessing the
+    /// MIME type from its extension.
+    pub fn from_file_data<P: AsRef<Path>>(path: P, data: Vec<u8>) -> Payload {
+        let path = path.as_ref();
+        Payload {
+            filename: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+            mime_type: guess_mime_type(path).to_string(),
+            data,
+        }
+    }
+
+    /// The original filename, if one was recorded
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    /// A hint of the kind of data stored
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    /// The payload bytes
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consumes the `Payload`, returning its bytes
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Returns this payload as an envelope:
+    ///
+    /// 1. Envelope version *(1 byte)*
+    /// 2. Filename length and UTF-8 filename, empty if unknown *(2 + n bytes)*
+    /// 3. MIME type length and MIME type *(2 + n bytes)*
+    /// 4. Size of the data *(8 bytes)*
+    /// 5. The data itself
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let filename = self.filename.as_deref().unwrap_or_default();
+        let mut bytes = Vec::with_capacity(
+            1 + 2 + filename.len() + 2 + self.mime_type.len() + 8 + self.data.len(),
+        );
+        bytes.push(VERSION);
+        push_field(&mut bytes, filename);
+        push_field(&mut bytes, &self.mime_type);
+        bytes.extend_from_slice(&(self.data.len() as u64).to_be_bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for Payload {
+    type Error = PngError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        let mut rest = bytes;
+        let version = take(&mut rest, 1)?[0];
+        if version != VERSION {
+            return Err(invalid_payload(&format!(
+                "unsupported payload version {}",
+                version
+            )));
+        }
+
+        let filename = take_field(&mut rest)?;
+        let mime_type = take_field(&mut rest)?;
+        let size = take(&mut rest, 8)?;
+        let size = u64::from_be_bytes(size.try_into().expect("took 8 bytes"));
+        if rest.len() as u64 != size {
+            return Err(invalid_payload(&format!(
+                "expected {} bytes of data, found {}",
+                size,
+                rest.len()
+            )));
+        }
+
+        Ok(Payload {
+            filename: (!filename.is_empty()).then_some(filename),
+            mime_type,
+            data: rest.to_vec(),
+        })
+    }
+}
+
+/// Guesses a MIME type from the extension of `path`.
+pub fn guess_mime_type<P: AsRef<Path>>(path: P) -> &'static str {
+    let extension = path
+        .as_ref()
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("txt") | Some("md") => "text/plain",
+        Some("json") => "application/json",
+        Some("pdf") => "application/pdf",
+        Some("zip") => "application/zip",
+        Some("gz") | Some("tgz") => "application/gzip",
+        Some("tar") => "application/x-tar",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("pem") => "application/x-pem-file",
+        _ => Payload::DEFAULT_MIME_TYPE,
+    }
+}
+
+fn invalid_payload(reason: &str) -> PngError {
+    PngError::InvalidPayload(reason.to_string())
+}
+
+fn push_field(bytes: &mut Vec<u8>, field: &str) {
+    // Fields longer than u16::MAX are cut at a character boundary
+    let mut end = field.len().min(u16::MAX as usize);
+    while !field.is_char_boundary(end) {
+        end -= 1;
+    }
+    bytes.extend_from_slice(&(end as u16).to_be_bytes());
+    bytes.extend_from_slice(&field.as_bytes()[..end]);
+}
+
+fn take<'a>(rest: &mut &'a [u8], length: usize) -> Result<&'a [u8]> {
+    if rest.len() < length {
+        return Err(invalid_payload("envelope is truncated"));
+    }
+    let (taken, remaining) = rest.split_at(length);
+    *rest = remaining;
+    Ok(taken)
+}
+
+fn take_field(rest: &mut &[u8]) -> Result<String> {
+    let length = take(rest, 2)?;
+    let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+    let field = std::str::from_utf8(take(rest, length)?)?;
+    Ok(field.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let data: Vec<u8> = (0..=255).collect();
+        let payload = Payload::from_file_data("/tmp/keys/backup.tar.gz", data.clone());
+        assert_eq!(payload.filename(), Some("backup.tar.gz"));
+        assert_eq!(payload.mime_type(), "application/gzip");
+
+        let bytes = payload.as_bytes();
+        let parsed = Payload::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(parsed, payload);
+        assert_eq!(parsed.into_data(), data);
+    }
+
+    #[test]
+    fn test_anonymous_payload() {
+        let payload = Payload::new(b"from stdin".to_vec());
+        let parsed = Payload::try_from(payload.as_bytes().as_slice()).unwrap();
+        assert_eq!(parsed.filename(), None);
+        assert_eq!(parsed.mime_type(), Payload::DEFAULT_MIME_TYPE);
+        assert_eq!(parsed.data(), b"from stdin");
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        let bytes = Payload::new(b"some data".to_vec()).as_bytes();
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(matches!(
+            Payload::try_from(truncated),
+            Err(PngError::InvalidPayload(_))
+        ));
+        assert!(matches!(
+            Payload::try_from(&bytes[..6]),
+            Err(PngError::InvalidPayload(_))
+        ));
+    }
+
+    #[test]
+    fn test_not_a_payload() {
+        assert!(Payload::try_from(&b"plain message"[..]).is_err());
+    }
+
+    #[test]
+    fn test_guess_mime_type() {
+        assert_eq!(guess_mime_type("notes.TXT"), "text/plain");
+        assert_eq!(guess_mime_type("id_ed25519"), Payload::DEFAULT_MIME_TYPE);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::error::{PngError, Result};
+use crate::ihdr::{ColorType, Ihdr};
+
+/// How the samples of a pixel are laid out in a scanline, as derived from IHDR.
+/// Samples of 16 bits are big endian, and pixels smaller than a byte are packed with the
+/// leftmost pixel in the high bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelFormat {
+    color_type: ColorType,
+    bit_depth: u8,
+}
+
+impl PixelFormat {
+    /// Creates a `PixelFormat`, failing if the PNG spec doesn't allow `bit_depth` for
+    /// `color_type`.
+    pub fn new(color_type: ColorType, bit_depth: u8) -> Result<PixelFormat> {
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(PngError::UnsupportedImage(format!(
+                "bit depth {} is not allowed for color type {}",
+                bit_depth, color_type
+            )));
+        }
+        Ok(PixelFormat {
+            color_type,
+            bit_depth,
+        })
+    }
+
+    /// The `ColorType` of each pixel
+    pub fn color_type(&self) -> ColorType {
+        self.color_type
+    }
+
+    /// The number of bits per sample, or per palette index for indexed images
+    pub fn bit_depth(&self) -> u8 {
+        self.bit_depth
+    }
+
+    /// The number of samples per pixel
+    pub fn channels(&self) -> usize {
+        self.color_type.channels()
+    }
+
+    /// The number of bits used by one pixel
+    pub fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+
+    /// The number of bytes used by one pixel, rounded up to 1 for pixels smaller than a
+    /// byte. This is the distance the scanline filters look back.
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.bits_per_pixel().div_ceil(8)
+    }
+
+    /// The number of bytes in an unfiltered scanline of `width` pixels.
+    pub fn stride(&self, width: u32) -> usize {
+        (width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+}
+
+impl From<&Ihdr> for PixelFormat {
+    fn from(ihdr: &Ihdr) -> Self {
+        PixelFormat {
+            color_type: ihdr.color_type(),
+            bit_depth: ihdr.bit_depth(),
+        }
+    }
+}
+
+/// A decoded, non-interlaced image: the unfiltered scanlines back to back, without
+/// their filter type bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pixels {
+    width: u32,
+    height: u32,
+    format: PixelFormat,
+    data: Vec<u8>,
+}
+
+impl Pixels {
+    /// Wraps raw scanlines, failing if the image is empty or `data` is not exactly
+    /// `height` scanlines long.
+    pub fn new(width: u32, height: u32, format: PixelFormat, data: Vec<u8>) -> Result<Pixels> {
+        if width == 0 || height == 0 {
+            return Err(PngError::InvalidImageData(format!(
+                "image is {}x{} pixels, both dimensions must be at least 1",
+                width, height
+            )));
+        }
+        let expected = format.stride(width) * height as usize;
+        if data.len() != expected {
+            return Err(PngError::InvalidImageData(format!(
+                "expected {} bytes of pixels, found {}",
+                expected,
+                data.len()
+            )));
+        }
+        Ok(Pixels {
+            width,
+            height,
+            format,
+            data,
+        })
+    }
+
+    /// The image width in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The image height in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The `PixelFormat` of `data`
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// The number of bytes in each scanline
+    pub fn stride(&self) -> usize {
+        self.format.stride(self.width)
+    }
+
+    /// The raw scanlines
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The raw scanlines, for editing in place
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Consumes the `Pixels`, returning the raw scanlines
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// The scanline at row `y`
+    pub fn row(&self, y: u32) -> &[u8] {
+        let stride = self.stride();
+        &self.data[y as usize * stride..(y as usize + 1) * stride]
+    }
+
+    /// The value of sample `channel` of the pixel at (`x`, `y`), at the image's bit depth.
+    /// Panics if the position or channel is out of bounds.
+    pub fn sample(&self, x: u32, y: u32, channel: usize) -> u16 {
+        assert!(x < self.width && y < self.height && channel < self.format.channels());
+        let row = self.row(y);
+        let bit_depth = self.format.bit_depth as usize;
+        let index = x as usize * self.format.channels() + channel;
+        match bit_depth {
+            16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+            8 => row[index] as u16,
+            _ => {
+                let bit = index * bit_depth;
+                let shift = 8 - bit_depth - bit % 8;
+                ((row[bit / 8] >> shift) & ((1 << bit_depth) - 1)) as u16
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pixel_format() {
+        let rgba16 = PixelFormat::new(ColorType::Rgba, 16).unwrap();
+        assert_eq!(rgba16.bits_per_pixel(), 64);
+        assert_eq!(rgba16.bytes_per_pixel(), 8);
+        assert_eq!(rgba16.stride(3), 24);
+
+        let indexed = PixelFormat::new(ColorType::Indexed, 2).unwrap();
+        assert_eq!(indexed.bytes_per_pixel(), 1);
+        assert_eq!(indexed.stride(5), 2);
+
+        assert!(PixelFormat::new(ColorType::Rgb, 4).is_err());
+    }
+
+    #[test]
+    fn test_samples() {
+        let gray4 = PixelFormat::new(ColorType::Grayscale, 4).unwrap();
+        let pixels = Pixels::new(3, 1, gray4, vec![0x1f, 0xa0]).unwrap();
+        assert_eq!(pixels.sample(0, 0, 0), 0x1);
+        assert_eq!(pixels.sample(1, 0, 0), 0xf);
+        assert_eq!(pixels.sample(2, 0, 0), 0xa);
+
+        let rgb16 = PixelFormat::new(ColorType::Rgb, 16).unwrap();
+        let pixels = Pixels::new(1, 1, rgb16, vec![0, 1, 2, 3, 4, 5]).unwrap();
+        assert_eq!(pixels.sample(0, 0, 1), 0x0203);
+    }
+
+    #[test]
+    fn test_wrong_length() {
+        let rgb = PixelFormat::new(ColorType::Rgb, 8).unwrap();
+        assert!(matches!(
+            Pixels::new(2, 2, rgb, vec![0; 11]),
+            Err(PngError::InvalidImageData(_))
+        ));
+    }
+
+    #[test]
+    fn test_zero_dimensions() {
+        let rgb = PixelFormat::new(ColorType::Rgb, 8).unwrap();
+        for (width, height) in [(0, 2), (2, 0), (0, 0)] {
+            assert!(matches!(
+                Pixels::new(width, height, rgb, Vec::new()),
+                Err(PngError::InvalidImageData(_))
+            ));
+        }
+    }
+}
@@ -1,16 +1,33 @@
 use std::convert::TryFrom;
 use std::fmt;
-use std::fs;
 use std::fs::File;
-use std::io::Cursor;
-use std::io::{Read, SeekFrom, Seek};
+use std::io::{BufReader, Read, Write};
 use std::path::Path;
 
-
-
+use crate::apng::{self, AnimationControl, Frame};
 use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::error::{PngError, Result};
+use crate::idat::{self, IdatOptions};
+use crate::ihdr::{Ihdr, InterlaceMethod};
+use crate::parse::{CrcPolicy, Diagnostic, ParseOptions};
+use crate::pixels::Pixels;
+use crate::reader::PngReader;
+use crate::text::parse_text;
+use crate::validate::{validate_chunks, Violation};
+use crate::writer::PngWriter;
 
-use crate::{Error, Result};
+/// Where `Png::insert_chunk` places a new chunk relative to the image's critical chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkPosition {
+    /// Immediately before IEND. Valid for any ancillary chunk.
+    #[default]
+    BeforeIend,
+    /// Immediately after IHDR, before any other chunk.
+    AfterIhdr,
+    /// Immediately before the first IDAT chunk.
+    BeforeFirstIdat,
+}
 
 /// A PNG container as described by the PNG spec
 /// http://www.libpng.org/pub/png/spec/1.2/PNG-Contents.html
@@ -34,12 +51,85 @@ impl Png {
 
     /// Creates a `Png` from a file path
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let mut f = File::open(&path)?;
-        let meatadata = fs::metadata(&path)?;
-        let mut buffer = vec![0u8; meatadata.len() as usize];
-        f.read(&mut buffer)?;
+        let file = File::open(path)?;
+
+        Png::from_reader(BufReader::new(file))
+    }
+
+    /// Creates a `Png` by reading every chunk from `reader`. See `PngReader` to process
+    /// the chunks one at a time instead.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
+        let chunks = PngReader::new(reader)?.collect::<Result<Vec<Chunk>>>()?;
 
-        Png::try_from(buffer.as_ref())
+        Ok(Png::from_chunks(chunks))
+    }
+
+    /// Creates a `Png` from a file path, reading it according to `options`.
+    pub fn from_file_with_options<P: AsRef<Path>>(
+        path: P,
+        options: &ParseOptions,
+    ) -> Result<(Self, Vec<Diagnostic>)> {
+        let file = File::open(path)?;
+
+        Png::from_reader_with_options(BufReader::new(file), options)
+    }
+
+    /// Creates a `Png` by reading every chunk from `reader`, tolerating the damage that
+    /// `options` allows. Problems that were tolerated are returned as `Diagnostic`s.
+    pub fn from_reader_with_options<R: Read>(
+        reader: R,
+        options: &ParseOptions,
+    ) -> Result<(Self, Vec<Diagnostic>)> {
+        let mut png_reader = PngReader::new(reader)?;
+        let mut chunks = Vec::new();
+        let mut diagnostics = Vec::new();
+        loop {
+            let offset = png_reader.offset();
+            let (chunk, stored) = match png_reader.read_chunk_unchecked() {
+                Ok(Some(read)) => read,
+                Ok(None) => break,
+                Err(PngError::TruncatedChunk { offset }) if options.allow_truncated => {
+                    diagnostics.push(Diagnostic::TruncatedChunk { offset });
+                    break;
+                }
+                Err(error) => return Err(error),
+            };
+
+            match options.crc_policy {
+                CrcPolicy::Strict => chunk.check_crc(stored, offset)?,
+                CrcPolicy::Warn if stored != chunk.crc() => {
+                    diagnostics.push(Diagnostic::CrcMismatch {
+                        chunk_type: *chunk.chunk_type(),
+                        offset,
+                        stored,
+                        computed: chunk.crc(),
+                    })
+                }
+                CrcPolicy::Warn | CrcPolicy::Ignore => (),
+            }
+            chunks.push(chunk);
+        }
+
+        let png = Png::from_chunks(chunks);
+        match png.position_of(ChunkType::IEND) {
+            None => diagnostics.push(Diagnostic::MissingIend),
+            Some(iend) if iend + 1 < png.chunks.len() => {
+                diagnostics.push(Diagnostic::ChunksAfterIend {
+                    count: png.chunks.len() - iend - 1,
+                })
+            }
+            Some(_) => (),
+        }
+        Ok((png, diagnostics))
+    }
+
+    /// Appends an empty IEND chunk if there is none, returning whether one was added.
+    pub fn add_missing_iend(&mut self) -> bool {
+        if self.position_of(ChunkType::IEND).is_some() {
+            return false;
+        }
+        self.append_chunk(Chunk::new(ChunkType::IEND, Vec::new()));
+        true
     }
 
     /// Appends a chunk to the end of this `Png` file's `Chunk` list.
@@ -47,27 +137,62 @@ impl Png {
         self.chunks.push(chunk);
     }
 
+    /// Inserts a chunk at the given `position`. Returns `PngError::ChunkNotFound` if the
+    /// chunk that `position` is relative to does not exist.
+    pub fn insert_chunk(&mut self, position: ChunkPosition, chunk: Chunk) -> Result<()> {
+        self.insert_chunks(position, vec![chunk])
+    }
+
+    /// Inserts several chunks, keeping their order, at the given `position`. Returns
+    /// `PngError::ChunkNotFound` if the chunk that `position` is relative to does not exist.
+    pub fn insert_chunks(&mut self, position: ChunkPosition, chunks: Vec<Chunk>) -> Result<()> {
+        let (anchor, offset) = match position {
+            ChunkPosition::BeforeIend => (ChunkType::IEND, 0),
+            ChunkPosition::AfterIhdr => (ChunkType::IHDR, 1),
+            ChunkPosition::BeforeFirstIdat => (ChunkType::IDAT, 0),
+        };
+        let index = self
+            .position_of(anchor)
+            .ok_or_else(|| PngError::ChunkNotFound(anchor.to_string()))?;
+
+        let index = index + offset;
+        self.chunks.splice(index..index, chunks);
+        self.edited();
+        Ok(())
+    }
+
     /// Searches for a `Chunk` with the specified `chunk_type` and removes the first
     /// matching `Chunk` from this `Png` list of chunks.
     pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
         let chunk = self
             .chunks
             .iter()
-            .position(|chunk| chunk.chunk_type().to_string() == chunk_type);
-        match chunk {
-            Some(index) => return Ok(self.chunks.remove(index)),
-            None => {
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "Chunk does not exist",
-                )))
-            }
+            .position(|chunk| chunk.chunk_type().as_str() == chunk_type);
+        let chunk = match chunk {
+            Some(index) => self.chunks.remove(index),
+            None => return Err(PngError::ChunkNotFound(chunk_type.to_string())),
+        };
+        self.edited();
+        Ok(chunk)
+    }
+
+    /// Removes every `Chunk` with the specified `chunk_type`, returning them in file order.
+    /// Returns `PngError::ChunkNotFound` if there are none.
+    pub fn remove_chunks(&mut self, chunk_type: &str) -> Result<Vec<Chunk>> {
+        let (removed, kept) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .partition(|chunk| chunk.chunk_type().as_str() == chunk_type);
+        self.chunks = kept;
+        if removed.is_empty() {
+            return Err(PngError::ChunkNotFound(chunk_type.to_string()));
         }
+        self.edited();
+        Ok(removed)
     }
 
     /// The header of this PNG.
     pub fn header(&self) -> &[u8; 8] {
-        &self.header 
+        &self.header
     }
 
     /// Lists the `Chunk`s stored in this `Png`
@@ -75,71 +200,252 @@ impl Png {
         &self.chunks
     }
 
+    /// The byte offset of each chunk's length field in the serialized file.
+    pub fn chunk_offsets(&self) -> Vec<u64> {
+        self.chunks
+            .iter()
+            .scan(self.header.len() as u64, |offset, chunk| {
+                let chunk_offset = *offset;
+                // Chunk data length + length (4 bytes) + chunk_type (4 bytes) + crc (4 bytes)
+                *offset += chunk.length() as u64 + 12;
+                Some(chunk_offset)
+            })
+            .collect()
+    }
+
     /// Searches for a `Chunk` with the specified `chunk_type` and returns the first
     /// matching `Chunk` from this `Png`.
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
-        let chunk = self
-            .chunks
+        self.chunks
+            .iter()
+            .find(|chunk| chunk.chunk_type().as_str() == chunk_type)
+    }
+
+    /// Returns every `Chunk` with the specified `chunk_type`, in file order.
+    pub fn chunks_by_type(&self, chunk_type: &str) -> Vec<&Chunk> {
+        self.chunks
             .iter()
-            .find(|chunk| chunk.chunk_type().to_string() == chunk_type);
-        match chunk {
-            Some(found) => return Some(found),
-            None => return None
+            .filter(|chunk| chunk.chunk_type().as_str() == chunk_type)
+            .collect()
+    }
+
+    /// Returns the text stored under `keyword` in the first matching tEXt, zTXt or iTXt
+    /// chunk, decompressing it if needed. Text chunks that fail to parse are skipped.
+    pub fn text_by_keyword(&self, keyword: &str) -> Result<String> {
+        self.find_text(|_| true, keyword)
+            .ok_or_else(|| PngError::ChunkNotFound(format!("text chunk \"{}\"", keyword)))
+    }
+
+    /// Like `text_by_keyword`, but only looks in chunks of `chunk_type`.
+    pub fn text_by_type_and_keyword(&self, chunk_type: ChunkType, keyword: &str) -> Result<String> {
+        self.find_text(|found| *found == chunk_type, keyword)
+            .ok_or_else(|| PngError::ChunkNotFound(format!("{} chunk \"{}\"", chunk_type, keyword)))
+    }
+
+    fn find_text<F>(&self, wanted: F, keyword: &str) -> Option<String>
+    where
+        F: Fn(&ChunkType) -> bool,
+    {
+        self.chunks
+            .iter()
+            .filter(|chunk| wanted(chunk.chunk_type()))
+            .find_map(|chunk| match parse_text(chunk) {
+                Some(Ok((chunk_keyword, text))) if chunk_keyword == keyword => Some(text),
+                _ => None,
+            })
+    }
+
+    /// Removes every `chunk_type` text chunk stored under `keyword`, returning how many were
+    /// removed. Text chunks that fail to parse are kept.
+    pub fn remove_text(&mut self, chunk_type: ChunkType, keyword: &str) -> usize {
+        let before = self.chunks.len();
+        self.chunks.retain(|chunk| {
+            *chunk.chunk_type() != chunk_type
+                || !matches!(parse_text(chunk), Some(Ok((chunk_keyword, _))) if chunk_keyword == keyword)
+        });
+        let removed = before - self.chunks.len();
+        if removed > 0 {
+            self.edited();
         }
+        removed
     }
 
-    /// Returns this `Png` as a byte sequence.
-    /// These bytes will contain the header followed by the bytes of all of the chunks.
-    pub fn as_bytes(&self) -> Vec<u8> {
-        let chunks: Vec<u8> = self
-            .chunks()
+    /// Parses the IHDR chunk, which describes the image dimensions and pixel format.
+    pub fn ihdr(&self) -> Result<Ihdr> {
+        let index = self
+            .position_of(ChunkType::IHDR)
+            .ok_or_else(|| PngError::ChunkNotFound(ChunkType::IHDR.to_string()))?;
+        Ihdr::try_from(&self.chunks[index])
+    }
+
+    /// Decodes the image: joins the consecutive IDAT chunks, inflates them and reverses
+    /// the scanline filters and any interlacing.
+    pub fn decode_pixels(&self) -> Result<Pixels> {
+        let ihdr = self.ihdr()?;
+        let first = self
+            .position_of(ChunkType::IDAT)
+            .ok_or_else(|| PngError::ChunkNotFound(ChunkType::IDAT.to_string()))?;
+        let run = self.chunks[first..]
             .iter()
-            .cloned()
-            .map(|chunk| chunk.as_bytes())
-            .flatten()
-            .collect();
-        self
-            .header()
-            .to_vec()
+            .take_while(|chunk| *chunk.chunk_type() == ChunkType::IDAT)
+            .count();
+        let count = self
+            .chunks
             .iter()
-            .cloned()
-            .chain(chunks)
-            .collect()
+            .filter(|chunk| *chunk.chunk_type() == ChunkType::IDAT)
+            .count();
+        if count != run {
+            return Err(PngError::InvalidImageData(
+                "IDAT chunks are not consecutive".to_string(),
+            ));
+        }
+
+        let compressed: Vec<u8> = self.chunks[first..first + run]
+            .iter()
+            .flat_map(|chunk| chunk.data().iter().copied())
+            .collect();
+        idat::decode(&ihdr, &compressed)
     }
-}
 
-impl TryFrom<&[u8]> for Png {
-    type Error = Error;
+    /// Re-encodes `pixels` into IDAT chunks that take the place of the existing IDAT run.
+    /// The image is always written without interlacing, and IHDR is rewritten to match
+    /// `pixels`.
+    pub fn encode_pixels(&mut self, pixels: &Pixels, options: &IdatOptions) -> Result<()> {
+        let format = pixels.format();
+        let ihdr = Ihdr::new(
+            pixels.width(),
+            pixels.height(),
+            format.bit_depth(),
+            format.color_type(),
+            InterlaceMethod::None,
+        )?;
+        let ihdr_index = self
+            .position_of(ChunkType::IHDR)
+            .ok_or_else(|| PngError::ChunkNotFound(ChunkType::IHDR.to_string()))?;
+        let index = self
+            .position_of(ChunkType::IDAT)
+            .ok_or_else(|| PngError::ChunkNotFound(ChunkType::IDAT.to_string()))?;
 
-    fn try_from(bytes: &[u8]) -> Result<Png> {
-        let length: u64 = bytes.len() as u64;
-        let mut reader = Cursor::new(bytes);
-        let mut buffer= vec![0u8; 8];
+        self.chunks[ihdr_index] = ihdr.to_chunk();
+        self.chunks
+            .retain(|chunk| *chunk.chunk_type() != ChunkType::IDAT);
+        self.chunks
+            .splice(index..index, idat::encode(pixels, options));
+        self.edited();
+        Ok(())
+    }
+
+    /// Parses the acTL chunk, returning `None` if this is not an animated PNG.
+    pub fn animation_control(&self) -> Result<Option<AnimationControl>> {
+        self.position_of(ChunkType::acTL)
+            .map(|index| AnimationControl::try_from(&self.chunks[index]))
+            .transpose()
+    }
 
-        reader.read_exact(&mut buffer)?;
+    /// Lists the frames of an animated PNG in file order. Empty if there are no fcTL
+    /// chunks.
+    pub fn frames(&self) -> Result<Vec<Frame<'_>>> {
+        apng::frames(&self.chunks)
+    }
+
+    /// Rewrites the APNG sequence numbers to count up from 0 in file order, and the
+    /// acTL frame count to match the fcTL chunks. Returns whether anything changed.
+    /// Chunk edits through `Png` renumber the sequence of animated PNGs automatically,
+    /// but only this changes the frame count.
+    pub fn renumber_frames(&mut self) -> bool {
+        apng::renumber(&mut self.chunks)
+    }
 
-        if buffer != Png::STANDARD_HEADER {
-            return Err(Box::new(std::io::Error::new(std::io::ErrorKind::InvalidData, "PNG header invalid")));
+    /// Moves the chunks that follow IEND, such as the messages that older versions of
+    /// pngme appended, in front of it, keeping their order. Returns how many were moved.
+    pub fn move_chunks_after_iend(&mut self) -> usize {
+        let iend = match self.position_of(ChunkType::IEND) {
+            Some(iend) => iend,
+            None => return 0,
+        };
+        let trailing: Vec<Chunk> = self.chunks.drain(iend + 1..).collect();
+        let moved = trailing.len();
+        self.chunks.splice(iend..iend, trailing);
+        moved
+    }
+
+    /// Tidies up after an edit: the sequence numbers of animated PNGs are renumbered.
+    fn edited(&mut self) {
+        if self.position_of(ChunkType::acTL).is_some() {
+            apng::renumber_sequence(&mut self.chunks);
         }
+    }
+
+    /// The index of the first chunk of `chunk_type`.
+    fn position_of(&self, chunk_type: ChunkType) -> Option<usize> {
+        self.chunks
+            .iter()
+            .position(|chunk| *chunk.chunk_type() == chunk_type)
+    }
+
+    /// Checks the chunk list against the PNG 1.2 ordering rules, returning every
+    /// violation found. An empty list means the structure is valid.
+    pub fn validate(&self) -> Vec<Violation> {
+        validate_chunks(&self.chunks)
+    }
 
-        let mut chunks: Vec<Chunk> = Vec::new();
-        while reader.seek(SeekFrom::Current(0)).unwrap() != length {
-            let current = reader.seek(SeekFrom::Current(0)).unwrap();
-
-            // Chunk data length
-            let mut chunk_data_buffer: [u8; 4] = [0u8;4];
-            reader.read_exact(&mut chunk_data_buffer)?;
-
-            // Chunk data length + lengh (4 bytes) + chunk_type (4 bytes)
-            // + crc (4 bytes)
-            let data_length = u32::from_be_bytes(chunk_data_buffer) + 12;
-            let mut chunk_buffer = vec![0u8; data_length as usize];
-            reader.set_position(current);
-            reader.read_exact(&mut chunk_buffer)?;
-            chunks.push(Chunk::try_from(chunk_buffer.as_ref()).unwrap());
+    /// Returns this `Png` as a byte sequence.
+    /// These bytes will contain the header followed by the bytes of all of the chunks.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        // Chunk data length + length (4 bytes) + chunk_type (4 bytes) + crc (4 bytes)
+        let length = self.header.len()
+            + self
+                .chunks
+                .iter()
+                .map(|chunk| chunk.data().len() + 12)
+                .sum::<usize>();
+        let mut bytes = Vec::with_capacity(length);
+        bytes.extend_from_slice(&self.header);
+        for chunk in &self.chunks {
+            chunk.extend_bytes(&mut bytes);
         }
+        bytes
+    }
 
-        Ok(Png { chunks, header: Png::STANDARD_HEADER})
+    /// Streams this `Png` to `writer` without building an in-memory copy of the file.
+    /// Fails with `PngError::ChunkAfterIend` if any chunk follows IEND, see
+    /// `move_chunks_after_iend` and `write_with_trailing_to`.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        let mut png_writer = PngWriter::new(writer)?;
+        for chunk in self.chunks() {
+            png_writer.write_chunk(chunk)?;
+        }
+        png_writer.into_inner()?;
+        Ok(())
+    }
+
+    /// Like `write_to`, but chunks that follow IEND are written after it as they are
+    /// instead of being rejected, so saving an edited file keeps data the edit didn't
+    /// touch.
+    pub fn write_with_trailing_to<W: Write>(&self, writer: W) -> Result<()> {
+        let end = self
+            .position_of(ChunkType::IEND)
+            .map_or(self.chunks.len(), |iend| iend + 1);
+        let mut png_writer = PngWriter::new(writer)?;
+        for chunk in &self.chunks[..end] {
+            png_writer.write_chunk(chunk)?;
+        }
+        let mut writer = png_writer.into_inner()?;
+        let mut trailing = Vec::new();
+        for chunk in &self.chunks[end..] {
+            chunk.extend_bytes(&mut trailing);
+        }
+        writer.write_all(&trailing)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl TryFrom<&[u8]> for Png {
+    type Error = PngError;
+
+    fn try_from(bytes: &[u8]) -> Result<Png> {
+        Png::from_reader(bytes)
     }
 }
 
@@ -147,6 +453,15 @@ impl fmt::Display for Png {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Png {{",)?;
         writeln!(f, "   Chunks: {}", self.chunks().len())?;
+        if let Ok(ihdr) = self.ihdr() {
+            writeln!(f, "   Width: {}", ihdr.width())?;
+            writeln!(f, "   Height: {}", ihdr.height())?;
+            writeln!(f, "   Bit depth: {}", ihdr.bit_depth())?;
+            writeln!(f, "   Color type: {}", ihdr.color_type())?;
+            writeln!(f, "   Compression method: {}", ihdr.compression_method())?;
+            writeln!(f, "   Filter method: {}", ihdr.filter_method())?;
+            writeln!(f, "   Interlace method: {}", ihdr.interlace_method())?;
+        }
         writeln!(f, "}}",)?;
         Ok(())
     }
@@ -157,16 +472,16 @@ mod tests {
     use super::*;
     use crate::chunk::Chunk;
     use crate::chunk_type::ChunkType;
-        use std::{convert::TryFrom};
+    use crate::ihdr::{ColorType, InterlaceMethod};
+    use crate::text::{CompressedTextChunk, TextChunk};
+    use std::convert::TryFrom;
 
     fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
-
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
-
-        chunks
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
@@ -249,6 +564,37 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_invalid_header_error() {
+        let png = Png::try_from(&[13, 80, 78, 71][..]);
+
+        assert!(matches!(png, Err(PngError::InvalidSignature(_))));
+    }
+
+    #[test]
+    fn test_invalid_chunk_reports_offset() {
+        let mut bytes = PNG_FILE.to_vec();
+        // Corrupt the last byte of the IHDR CRC
+        bytes[32] ^= 0xff;
+
+        match Png::try_from(bytes.as_ref()) {
+            Err(PngError::CrcMismatch {
+                chunk_type, offset, ..
+            }) => {
+                assert_eq!(chunk_type.to_string(), "IHDR");
+                assert_eq!(offset, 8);
+            }
+            other => panic!("expected CrcMismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_remove_missing_chunk() {
+        let mut png = testing_png();
+        let removed = png.remove_chunk("TeSt");
+        assert!(matches!(removed, Err(PngError::ChunkNotFound(_))));
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -273,6 +619,62 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_insert_chunk_before_iend() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.insert_chunk(
+            ChunkPosition::BeforeIend,
+            chunk_from_strings("TeSt", "Message").unwrap(),
+        )
+        .unwrap();
+        let types = chunk_types(&png);
+        assert_eq!(&types[types.len() - 2..], ["TeSt", "IEND"]);
+        assert!(png.write_to(Vec::new()).is_ok());
+    }
+
+    #[test]
+    fn test_insert_chunk_after_ihdr() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.insert_chunk(
+            ChunkPosition::AfterIhdr,
+            chunk_from_strings("TeSt", "Message").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(&chunk_types(&png)[..2], ["IHDR", "TeSt"]);
+    }
+
+    #[test]
+    fn test_insert_chunk_before_first_idat() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.insert_chunk(
+            ChunkPosition::BeforeFirstIdat,
+            chunk_from_strings("TeSt", "Message").unwrap(),
+        )
+        .unwrap();
+        let types = chunk_types(&png);
+        let index = types.iter().position(|t| t == "TeSt").unwrap();
+        assert_eq!(types[index + 1], "IDAT");
+        assert!(!types[..index].contains(&"IDAT".to_string()));
+    }
+
+    #[test]
+    fn test_insert_chunk_missing_anchor() {
+        let mut png = testing_png();
+        let result = png.insert_chunk(
+            ChunkPosition::BeforeIend,
+            chunk_from_strings("TeSt", "Message").unwrap(),
+        );
+        assert!(matches!(result, Err(PngError::ChunkNotFound(_))));
+        assert_eq!(png.chunks().len(), 3);
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
@@ -282,6 +684,53 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_chunk_offsets() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let offsets = png.chunk_offsets();
+        assert_eq!(offsets.len(), png.chunks().len());
+        assert_eq!(offsets[0], 8);
+        assert_eq!(offsets[1], 8 + 12 + 13);
+        assert_eq!(*offsets.last().unwrap(), PNG_FILE.len() as u64 - 12);
+    }
+
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("FrSt", "Again").unwrap());
+        let chunks = png.chunks_by_type("FrSt");
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(&chunks[1].data_as_string().unwrap(), "Again");
+        assert!(png.chunks_by_type("TeSt").is_empty());
+    }
+
+    #[test]
+    fn test_remove_chunks() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("FrSt", "Again").unwrap());
+        let removed = png.remove_chunks("FrSt").unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(png.chunks().len(), 2);
+        assert!(matches!(
+            png.remove_chunks("FrSt"),
+            Err(PngError::ChunkNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_insert_chunks_keeps_order() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let chunks = vec![
+            chunk_from_strings("teSt", "one").unwrap(),
+            chunk_from_strings("teSt", "two").unwrap(),
+        ];
+        png.insert_chunks(ChunkPosition::AfterIhdr, chunks).unwrap();
+        let inserted = png.chunks_by_type("teSt");
+        assert_eq!(&inserted[0].data_as_string().unwrap(), "one");
+        assert_eq!(&inserted[1].data_as_string().unwrap(), "two");
+        assert_eq!(&chunk_types(&png)[..3], ["IHDR", "teSt", "teSt"]);
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -292,10 +741,333 @@ mod tests {
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
-        let expected: Vec<u8> = PNG_FILE.iter().copied().collect();
+        let expected: Vec<u8> = PNG_FILE.to_vec();
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_write_to() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut actual = Vec::new();
+        png.write_to(&mut actual).unwrap();
+        assert_eq!(actual, PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_write_to_rejects_chunk_after_iend() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        let result = png.write_to(Vec::new());
+        assert!(matches!(result, Err(PngError::ChunkAfterIend(_))));
+    }
+
+    #[test]
+    fn test_edits_keep_chunks_after_iend() {
+        // Older versions of pngme appended the message after IEND
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(chunk_from_strings("ruSt", "Old message").unwrap());
+        png.insert_chunk(
+            ChunkPosition::BeforeIend,
+            chunk_from_strings("teSt", "New message").unwrap(),
+        )
+        .unwrap();
+        let types = chunk_types(&png);
+        assert_eq!(&types[types.len() - 3..], ["teSt", "IEND", "ruSt"]);
+        assert!(png.write_to(Vec::new()).is_err());
+
+        let mut bytes = Vec::new();
+        png.write_with_trailing_to(&mut bytes).unwrap();
+        assert_eq!(bytes, png.as_bytes());
+
+        assert_eq!(png.move_chunks_after_iend(), 1);
+        let types = chunk_types(&png);
+        assert_eq!(&types[types.len() - 3..], ["teSt", "ruSt", "IEND"]);
+        assert_eq!(png.move_chunks_after_iend(), 0);
+    }
+
+    #[test]
+    fn test_chunks_after_iend_diagnostic() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend(
+            chunk_from_strings("ruSt", "Old message")
+                .unwrap()
+                .as_bytes(),
+        );
+        let (_, diagnostics) =
+            Png::from_reader_with_options(bytes.as_slice(), &ParseOptions::lenient()).unwrap();
+        assert_eq!(diagnostics, [Diagnostic::ChunksAfterIend { count: 1 }]);
+    }
+
+    #[test]
+    fn test_validate_image_file() {
+        // The fixture carries a message in a critical "RuSt" chunk, which real decoders
+        // would refuse to render.
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(
+            png.validate(),
+            vec![Violation::UnknownCriticalChunk {
+                chunk_type: "RuSt".to_string(),
+                index: 5
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_chunk_after_iend() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.remove_chunk("RuSt").unwrap();
+        png.append_chunk(chunk_from_strings("teSt", "Message").unwrap());
+        let iend = png.chunks().len() - 2;
+        assert_eq!(png.validate(), vec![Violation::IendNotLast { index: iend }]);
+    }
+
+    #[test]
+    fn test_ihdr() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let ihdr = png.ihdr().unwrap();
+        assert_eq!(ihdr.width(), 50);
+        assert_eq!(ihdr.height(), 50);
+        assert_eq!(ihdr.bit_depth(), 8);
+        assert_eq!(ihdr.color_type(), ColorType::Rgba);
+        assert_eq!(ihdr.interlace_method(), InterlaceMethod::None);
+
+        let display = format!("{}", png);
+        assert!(display.contains("Width: 50"));
+        assert!(display.contains("Color type: RGBA"));
+    }
+
+    #[test]
+    fn test_missing_ihdr() {
+        let png = testing_png();
+        assert!(matches!(png.ihdr(), Err(PngError::ChunkNotFound(_))));
+    }
+
+    #[test]
+    fn test_text_by_keyword() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let comment = TextChunk::new("Comment", "first").unwrap();
+        let title = CompressedTextChunk::new("Title", "dice").unwrap();
+        png.insert_chunk(ChunkPosition::BeforeIend, comment.to_chunk())
+            .unwrap();
+        png.insert_chunk(ChunkPosition::BeforeIend, title.to_chunk())
+            .unwrap();
+
+        assert_eq!(png.text_by_keyword("Comment").unwrap(), "first");
+        assert_eq!(png.text_by_keyword("Title").unwrap(), "dice");
+        assert!(matches!(
+            png.text_by_keyword("Author"),
+            Err(PngError::ChunkNotFound(_))
+        ));
+
+        assert_eq!(
+            png.text_by_type_and_keyword(ChunkType::zTXt, "Title")
+                .unwrap(),
+            "dice"
+        );
+        assert!(png
+            .text_by_type_and_keyword(ChunkType::tEXt, "Title")
+            .is_err());
+
+        assert_eq!(png.remove_text(ChunkType::zTXt, "Comment"), 0);
+        assert_eq!(png.remove_text(ChunkType::tEXt, "Comment"), 1);
+        assert!(png.text_by_keyword("Comment").is_err());
+        assert_eq!(png.text_by_keyword("Title").unwrap(), "dice");
+    }
+
+    #[test]
+    fn test_text_by_keyword_skips_unparsable_chunks() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let broken = Chunk::new(ChunkType::zTXt, b"Broken\0\0not deflated".to_vec());
+        let comment = TextChunk::new("Comment", "found").unwrap();
+        png.insert_chunk(ChunkPosition::BeforeIend, broken).unwrap();
+        png.insert_chunk(ChunkPosition::BeforeIend, comment.to_chunk())
+            .unwrap();
+        assert_eq!(png.text_by_keyword("Comment").unwrap(), "found");
+    }
+
+    #[test]
+    fn test_decode_pixels() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let pixels = png.decode_pixels().unwrap();
+        assert_eq!((pixels.width(), pixels.height()), (50, 50));
+        assert_eq!(pixels.data().len(), 50 * 50 * 4);
+        assert_eq!(pixels.format(), png.ihdr().unwrap().pixel_format());
+    }
+
+    #[test]
+    fn test_decode_pixels_non_consecutive_idat() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let idat = png.chunk_by_type("IDAT").unwrap().clone();
+        png.insert_chunk(ChunkPosition::AfterIhdr, idat).unwrap();
+        assert!(matches!(
+            png.decode_pixels(),
+            Err(PngError::InvalidImageData(_))
+        ));
+    }
+
+    #[test]
+    fn test_encode_pixels() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut pixels = png.decode_pixels().unwrap();
+        pixels.data_mut()[0] ^= 1;
+        let options = IdatOptions::default().with_chunk_size(1024);
+        png.encode_pixels(&pixels, &options).unwrap();
+
+        let idat_count = png.chunks_by_type("IDAT").len();
+        assert!(idat_count > 1);
+        assert_eq!(
+            chunk_types(&png)[4..4 + idat_count],
+            vec!["IDAT"; idat_count]
+        );
+        assert_eq!(chunk_types(&png).last().unwrap(), "IEND");
+        assert_eq!(png.decode_pixels().unwrap(), pixels);
+        assert!(png
+            .validate()
+            .iter()
+            .all(|violation| matches!(violation, Violation::UnknownCriticalChunk { .. })));
+    }
+
+    #[test]
+    fn test_encode_pixels_updates_ihdr() {
+        let ihdr = Ihdr::new(9, 5, 8, ColorType::Rgb, InterlaceMethod::Adam7).unwrap();
+        let format = ihdr.pixel_format();
+        let pixels = Pixels::new(9, 5, format, vec![7; format.stride(9) * 5]).unwrap();
+        let mut png = Png::from_chunks(vec![
+            ihdr.to_chunk(),
+            chunk_from_strings("IDAT", "stale").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+
+        png.encode_pixels(&pixels, &IdatOptions::default()).unwrap();
+        assert_eq!(
+            png.ihdr().unwrap().interlace_method(),
+            InterlaceMethod::None
+        );
+        assert_eq!(png.decode_pixels().unwrap(), pixels);
+    }
+
+    #[test]
+    fn test_frames() {
+        use crate::apng::{BlendOp, DisposeOp, FrameControl, FrameData};
+
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.animation_control().unwrap().is_none());
+        assert!(png.frames().unwrap().is_empty());
+
+        let control = |sequence_number| {
+            FrameControl::new(
+                sequence_number,
+                50,
+                50,
+                0,
+                0,
+                1,
+                2,
+                DisposeOp::None,
+                BlendOp::Source,
+            )
+            .unwrap()
+            .to_chunk()
+        };
+        png.insert_chunks(
+            ChunkPosition::BeforeFirstIdat,
+            vec![AnimationControl::new(2, 0).unwrap().to_chunk(), control(0)],
+        )
+        .unwrap();
+        png.insert_chunks(
+            ChunkPosition::BeforeIend,
+            vec![control(1), FrameData::new(2, vec![1, 2]).to_chunk()],
+        )
+        .unwrap();
+        assert_eq!(png.animation_control().unwrap().unwrap().num_frames(), 2);
+        let frames = png.frames().unwrap();
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].is_default_image());
+        assert_eq!(frames[1].compressed_data(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_edits_renumber_frames() {
+        use crate::apng::FrameData;
+
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "").unwrap(),
+            AnimationControl::new(1, 0).unwrap().to_chunk(),
+            FrameData::new(5, Vec::new()).to_chunk(),
+            FrameData::new(9, Vec::new()).to_chunk(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        png.remove_chunk("fdAT").unwrap();
+        let remaining = FrameData::try_from(png.chunk_by_type("fdAT").unwrap()).unwrap();
+        assert_eq!(remaining.sequence_number(), 0);
+    }
+
+    /// Flips a bit in the data of the IDAT chunk of `PNG_FILE`, breaking its CRC.
+    fn corrupted_png_file() -> (Vec<u8>, u64) {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let idat_offset: usize = 8 + png.chunks()[..4]
+            .iter()
+            .map(|chunk| chunk.as_bytes().len())
+            .sum::<usize>();
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[idat_offset + 20] ^= 0x10;
+        (bytes, idat_offset as u64)
+    }
+
+    #[test]
+    fn test_crc_policies() {
+        let (bytes, idat_offset) = corrupted_png_file();
+        assert!(Png::try_from(bytes.as_slice()).is_err());
+
+        let strict = ParseOptions::default();
+        assert!(matches!(
+            Png::from_reader_with_options(bytes.as_slice(), &strict),
+            Err(PngError::CrcMismatch { offset, .. }) if offset == idat_offset
+        ));
+
+        let warn = ParseOptions {
+            crc_policy: CrcPolicy::Warn,
+            ..Default::default()
+        };
+        let (png, diagnostics) = Png::from_reader_with_options(bytes.as_slice(), &warn).unwrap();
+        assert_eq!(png.chunks().len(), 7);
+        assert!(matches!(
+            diagnostics.as_slice(),
+            [Diagnostic::CrcMismatch { offset, .. }] if *offset == idat_offset
+        ));
+
+        let ignore = ParseOptions {
+            crc_policy: CrcPolicy::Ignore,
+            ..Default::default()
+        };
+        let (_, diagnostics) = Png::from_reader_with_options(bytes.as_slice(), &ignore).unwrap();
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_truncated_and_missing_iend() {
+        let bytes = &PNG_FILE[..PNG_FILE.len() - 6];
+        assert!(matches!(
+            Png::from_reader_with_options(bytes, &ParseOptions::default()),
+            Err(PngError::TruncatedChunk { .. })
+        ));
+
+        let (mut png, diagnostics) =
+            Png::from_reader_with_options(bytes, &ParseOptions::lenient()).unwrap();
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic::TruncatedChunk {
+                    offset: PNG_FILE.len() as u64 - 12
+                },
+                Diagnostic::MissingIend
+            ]
+        );
+        assert!(png.add_missing_iend());
+        assert!(!png.add_missing_iend());
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
     #[test]
     fn test_png_trait_impls() {
         let chunk_bytes: Vec<u8> = testing_chunks()
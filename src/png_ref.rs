@@ -0,0 +1,201 @@
+use std::convert::TryFrom;
+
+use crate::chunk_ref::ChunkRef;
+use crate::chunk_type::ChunkType;
+use crate::error::{PngError, Result};
+use crate::png::Png;
+use crate::validate::{validate_chunk_refs, Violation};
+
+/// A PNG file borrowed from an in-memory buffer, such as a file read into a `Vec` or a
+/// memory-mapped file.
+///
+/// Parsing checks the signature and the layout of every chunk but copies no chunk data
+/// and leaves CRCs unchecked, which makes read-only scans over many files cheap. Use
+/// `check_crcs` to verify the CRCs and `to_png` for an owned, editable `Png`.
+#[derive(Debug, Clone)]
+pub struct PngRef<'a> {
+    chunks: Vec<ChunkRef<'a>>,
+}
+
+impl<'a> PngRef<'a> {
+    /// The chunks in file order
+    pub fn chunks(&self) -> &[ChunkRef<'a>] {
+        &self.chunks
+    }
+
+    /// Returns the first chunk with the specified `chunk_type`.
+    pub fn chunk_by_type(&self, chunk_type: ChunkType) -> Option<&ChunkRef<'a>> {
+        self.chunks
+            .iter()
+            .find(|chunk| *chunk.chunk_type() == chunk_type)
+    }
+
+    /// Returns every chunk with the specified `chunk_type`, in file order.
+    pub fn chunks_by_type(&self, chunk_type: ChunkType) -> Vec<&ChunkRef<'a>> {
+        self.chunks
+            .iter()
+            .filter(|chunk| *chunk.chunk_type() == chunk_type)
+            .collect()
+    }
+
+    /// Checks the CRC of every chunk, returning the first `PngError::CrcMismatch`.
+    pub fn check_crcs(&self) -> Result<()> {
+        self.chunks.iter().try_for_each(ChunkRef::check_crc)
+    }
+
+    /// Checks the chunk ordering rules, see `Png::validate`. Only the IHDR and APNG chunks
+    /// are read.
+    pub fn validate(&self) -> Vec<Violation> {
+        validate_chunk_refs(&self.chunks)
+    }
+
+    /// Copies every chunk into an owned `Png`. CRCs are not checked.
+    pub fn to_png(&self) -> Png {
+        Png::from_chunks(self.chunks.iter().map(ChunkRef::to_chunk).collect())
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for PngRef<'a> {
+    type Error = PngError;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self> {
+        let chunks = ChunkRefs::new(bytes)?.collect::<Result<Vec<ChunkRef>>>()?;
+        Ok(PngRef { chunks })
+    }
+}
+
+/// Iterates over the chunks of a PNG file in a buffer without copying them, the borrowed
+/// counterpart of `PngReader`.
+///
+/// The signature is checked when the iterator is created. Iteration stops after the first
+/// error. CRCs are not checked.
+#[derive(Debug, Clone)]
+pub struct ChunkRefs<'a> {
+    rest: &'a [u8],
+    offset: u64,
+    finished: bool,
+}
+
+impl<'a> ChunkRefs<'a> {
+    /// Creates a `ChunkRefs`, validating the 8 byte PNG signature at the start of `bytes`.
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        let header = Png::STANDARD_HEADER.len();
+        if !bytes.starts_with(&Png::STANDARD_HEADER) {
+            let mut found = [0u8; 8];
+            let length = bytes.len().min(header);
+            found[..length].copy_from_slice(&bytes[..length]);
+            return Err(PngError::InvalidSignature(found));
+        }
+
+        Ok(ChunkRefs {
+            rest: &bytes[header..],
+            offset: header as u64,
+            finished: false,
+        })
+    }
+
+    /// The byte offset of the next chunk in the buffer.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+impl<'a> Iterator for ChunkRefs<'a> {
+    type Item = Result<ChunkRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished || self.rest.is_empty() {
+            return None;
+        }
+        match ChunkRef::parse(self.rest, self.offset) {
+            Ok((chunk, rest)) => {
+                self.offset += (chunk.length() as usize + ChunkRef::OVERHEAD) as u64;
+                self.rest = rest;
+                Some(Ok(chunk))
+            }
+            Err(error) => {
+                self.finished = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use std::str::FromStr;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(
+                ChunkType::from_str("FrSt").unwrap(),
+                b"I am the first chunk".to_vec(),
+            ),
+            Chunk::new(
+                ChunkType::from_str("miDl").unwrap(),
+                b"I am another chunk".to_vec(),
+            ),
+            Chunk::new(
+                ChunkType::from_str("LASt").unwrap(),
+                b"I am the last chunk".to_vec(),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_matches_owned_parse() {
+        let png = testing_png();
+        let bytes = png.as_bytes();
+        let png_ref = PngRef::try_from(bytes.as_slice()).unwrap();
+
+        assert!(png_ref.check_crcs().is_ok());
+        assert_eq!(png_ref.to_png().as_bytes(), bytes);
+        let offsets: Vec<u64> = png_ref.chunks().iter().map(ChunkRef::offset).collect();
+        assert_eq!(offsets, png.chunk_offsets());
+        assert_eq!(
+            png_ref
+                .chunk_by_type(ChunkType::from_str("miDl").unwrap())
+                .unwrap()
+                .data(),
+            b"I am another chunk"
+        );
+        let last = ChunkType::from_str("LASt").unwrap();
+        assert_eq!(png_ref.chunks_by_type(last).len(), 1);
+    }
+
+    #[test]
+    fn test_bad_crc_found_by_check() {
+        let mut bytes = testing_png().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+
+        let png_ref = PngRef::try_from(bytes.as_slice()).unwrap();
+        assert!(matches!(
+            png_ref.check_crcs(),
+            Err(PngError::CrcMismatch { offset: 70, .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_signature() {
+        assert!(matches!(
+            PngRef::try_from(&[137, 80, 78][..]),
+            Err(PngError::InvalidSignature([137, 80, 78, 0, 0, 0, 0, 0]))
+        ));
+    }
+
+    #[test]
+    fn test_truncated_chunk_offset() {
+        let bytes = testing_png().as_bytes();
+        let mut chunks = ChunkRefs::new(&bytes[..bytes.len() - 2]).unwrap();
+        assert!(chunks.next().unwrap().is_ok());
+        assert!(chunks.next().unwrap().is_ok());
+        assert!(matches!(
+            chunks.next(),
+            Some(Err(PngError::TruncatedChunk { offset: 70 }))
+        ));
+        assert!(chunks.next().is_none());
+    }
+}
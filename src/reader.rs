@@ -0,0 +1,184 @@
+use std::io::{ErrorKind, Read};
+
+use crate::chunk::Chunk;
+use crate::error::{PngError, Result};
+use crate::png::Png;
+
+/// Reads a PNG stream one `Chunk` at a time from any `Read` source.
+///
+/// The signature is checked when the reader is created. Chunks are then yielded in
+/// file order through the `Iterator` implementation, so only a single chunk is held
+/// in memory at once. Iteration stops after the first error.
+#[derive(Debug)]
+pub struct PngReader<R: Read> {
+    reader: R,
+    offset: u64,
+    finished: bool,
+}
+
+impl<R: Read> PngReader<R> {
+    /// Creates a `PngReader`, consuming and validating the 8 byte PNG signature.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut header = [0u8; 8];
+        let read = read_fully(&mut reader, &mut header)?;
+        if read != header.len() || header != Png::STANDARD_HEADER {
+            return Err(PngError::InvalidSignature(header));
+        }
+
+        Ok(PngReader {
+            reader,
+            offset: header.len() as u64,
+            finished: false,
+        })
+    }
+
+    /// The byte offset of the next chunk in the stream.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Reads the next chunk. Returns `Ok(None)` when the stream ends cleanly on a
+    /// chunk boundary.
+    pub fn read_chunk(&mut self) -> Result<Option<Chunk>> {
+        let offset = self.offset;
+        match self.read_chunk_unchecked()? {
+            Some((chunk, crc)) => {
+                chunk.check_crc(crc, offset)?;
+                Ok(Some(chunk))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the next chunk along with the CRC stored in the stream, without checking it.
+    /// Returns `Ok(None)` when the stream ends cleanly on a chunk boundary.
+    pub fn read_chunk_unchecked(&mut self) -> Result<Option<(Chunk, u32)>> {
+        let offset = self.offset;
+        let mut length = [0u8; 4];
+        match read_fully(&mut self.reader, &mut length)? {
+            0 => return Ok(None),
+            4 => (),
+            _ => return Err(PngError::TruncatedChunk { offset }),
+        }
+
+        let (chunk, crc) = Chunk::read_unchecked_after_length(
+            &mut self.reader,
+            u32::from_be_bytes(length),
+            offset,
+        )?;
+        // Chunk data length + length (4 bytes) + chunk_type (4 bytes) + crc (4 bytes)
+        self.offset += chunk.length() as u64 + 12;
+        Ok(Some((chunk, crc)))
+    }
+
+    /// Unwraps this `PngReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for PngReader<R> {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let result = self.read_chunk().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.finished = true;
+        }
+        result
+    }
+}
+
+/// Reads until `buffer` is full or the stream ends, returning the number of bytes read.
+fn read_fully<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<usize> {
+    let mut read = 0;
+    while read < buffer.len() {
+        match reader.read(&mut buffer[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(error) if error.kind() == ErrorKind::Interrupted => (),
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn testing_bytes() -> Vec<u8> {
+        let chunks = vec![
+            Chunk::new(ChunkType::from_str("FrSt").unwrap(), b"first".to_vec()),
+            Chunk::new(ChunkType::from_str("LASt").unwrap(), b"last".to_vec()),
+        ];
+        Png::from_chunks(chunks).as_bytes()
+    }
+
+    /// A reader that hands out at most one byte per call, like a slow pipe.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_reads_chunks_in_order() {
+        let bytes = testing_bytes();
+        let reader = PngReader::new(bytes.as_slice()).unwrap();
+        let types: Vec<String> = reader
+            .map(|chunk| chunk.unwrap().chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["FrSt", "LASt"]);
+    }
+
+    #[test]
+    fn test_reads_from_short_reads() {
+        let bytes = testing_bytes();
+        let reader = PngReader::new(Trickle(&bytes)).unwrap();
+        assert_eq!(reader.count(), 2);
+    }
+
+    #[test]
+    fn test_tracks_offsets() {
+        let bytes = testing_bytes();
+        let mut reader = PngReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(reader.offset(), 8);
+        reader.read_chunk().unwrap();
+        assert_eq!(reader.offset(), 8 + 12 + 5);
+        reader.read_chunk().unwrap();
+        assert_eq!(reader.offset(), bytes.len() as u64);
+        assert!(reader.read_chunk().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_invalid_signature() {
+        let reader = PngReader::new(&b"GIF89a"[..]);
+        assert!(matches!(reader, Err(PngError::InvalidSignature(_))));
+    }
+
+    #[test]
+    fn test_truncated_chunk_offset() {
+        let bytes = testing_bytes();
+        let truncated = &bytes[..bytes.len() - 2];
+        let results: Vec<Result<Chunk>> = PngReader::new(truncated).unwrap().collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1],
+            Err(PngError::TruncatedChunk { offset: 25 })
+        ));
+    }
+}
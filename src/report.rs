@@ -0,0 +1,425 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
+
+use crate::chunk::Chunk;
+use crate::chunk_ref::ChunkRef;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::Ihdr;
+use crate::png::Png;
+use crate::png_ref::PngRef;
+use crate::text::{is_text_chunk_type, parse_text};
+use crate::time::ModificationTime;
+use crate::validate::Violation;
+
+/// The number of bytes on each line of a hex dump.
+const HEX_DUMP_WIDTH: usize = 16;
+
+/// A chunk as listed by `print`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ChunkReport<'a> {
+    pub index: usize,
+    /// Byte offset of the chunk's length field in the file
+    pub offset: u64,
+    pub chunk_type: &'a ChunkType,
+    pub length: u32,
+    /// The CRC stored in the file
+    pub crc: u32,
+    /// Whether `crc` matches the chunk type and data
+    pub crc_valid: bool,
+    pub critical: bool,
+    pub public: bool,
+    pub reserved_bit_valid: bool,
+    pub safe_to_copy: bool,
+    /// A one line description of the chunk's contents, for chunk types that are understood
+    pub summary: Option<String>,
+    /// The chunk data, only included when requested
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            skip_serializing_if = "Option::is_none",
+            serialize_with = "serialize_hex"
+        )
+    )]
+    pub data: Option<&'a [u8]>,
+}
+
+impl ChunkReport<'_> {
+    /// The property bits as `C`ritical, `P`ublic, `R`eserved bit valid and `S`afe to
+    /// copy, with `-` for each unset bit.
+    pub fn flags(&self) -> String {
+        [
+            (self.critical, 'C'),
+            (self.public, 'P'),
+            (self.reserved_bit_valid, 'R'),
+            (self.safe_to_copy, 'S'),
+        ]
+        .iter()
+        .map(|&(set, flag)| if set { flag } else { '-' })
+        .collect()
+    }
+}
+
+/// The structure of a PNG as printed by `print`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PngReport<'a> {
+    /// The parsed IHDR, or `None` if it is missing or malformed
+    pub ihdr: Option<Ihdr>,
+    pub chunks: Vec<ChunkReport<'a>>,
+}
+
+impl<'a> PngReport<'a> {
+    /// Describes every chunk of `png`, including the chunk data if `include_data` is set.
+    pub fn new(png: &'a Png, include_data: bool) -> PngReport<'a> {
+        let chunks = png
+            .chunks()
+            .iter()
+            .zip(png.chunk_offsets())
+            .enumerate()
+            .map(|(index, (chunk, offset))| {
+                let chunk_type = chunk.chunk_type();
+                ChunkReport {
+                    index,
+                    offset,
+                    chunk_type,
+                    length: chunk.length(),
+                    // An owned chunk is always written with the CRC of its contents
+                    crc: chunk.crc(),
+                    crc_valid: true,
+                    critical: chunk_type.is_critical(),
+                    public: chunk_type.is_public(),
+                    reserved_bit_valid: chunk_type.is_reserved_bit_valid(),
+                    safe_to_copy: chunk_type.is_safe_to_copy(),
+                    summary: summarize(chunk),
+                    data: include_data.then(|| chunk.data()),
+                }
+            })
+            .collect();
+        PngReport {
+            ihdr: png.ihdr().ok(),
+            chunks,
+        }
+    }
+
+    /// Describes every chunk of a borrowed `png`, like `new`, reporting the CRCs stored in
+    /// the file and whether they are correct.
+    pub fn from_ref(png: &'a PngRef<'_>, include_data: bool) -> PngReport<'a> {
+        let chunks = png
+            .chunks()
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let chunk_type = chunk.chunk_type();
+                ChunkReport {
+                    index,
+                    offset: chunk.offset(),
+                    chunk_type,
+                    length: chunk.length(),
+                    crc: chunk.stored_crc(),
+                    crc_valid: chunk.check_crc().is_ok(),
+                    critical: chunk_type.is_critical(),
+                    public: chunk_type.is_public(),
+                    reserved_bit_valid: chunk_type.is_reserved_bit_valid(),
+                    safe_to_copy: chunk_type.is_safe_to_copy(),
+                    summary: summarize_ref(chunk),
+                    data: include_data.then(|| chunk.data()),
+                }
+            })
+            .collect();
+        PngReport {
+            ihdr: png
+                .chunk_by_type(ChunkType::IHDR)
+                .and_then(|chunk| Ihdr::try_from(&chunk.to_chunk()).ok()),
+            chunks,
+        }
+    }
+}
+
+impl fmt::Display for PngReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>5}  {:>10}  {:<4}  {:>10}  {:<8}  {:<5}  Contents",
+            "Index", "Offset", "Type", "Length", "CRC", "Flags"
+        )?;
+        for chunk in &self.chunks {
+            write!(
+                f,
+                "{:>5}  {:>10}  {:<4}  {:>10}  {:08x}  {}",
+                chunk.index,
+                chunk.offset,
+                chunk.chunk_type,
+                chunk.length,
+                chunk.crc,
+                chunk.flags()
+            )?;
+            let contents: Vec<&str> = (!chunk.crc_valid)
+                .then_some("bad CRC")
+                .into_iter()
+                .chain(chunk.summary.as_deref())
+                .collect();
+            match contents.is_empty() {
+                true => writeln!(f)?,
+                // Flags are one character narrower than their column heading
+                false => writeln!(f, "   {}", contents.join("; "))?,
+            }
+            if let Some(data) = chunk.data {
+                // The data follows the length and chunk type fields
+                hex_dump(f, data, chunk.offset + 8)?;
+            }
+        }
+        write!(
+            f,
+            "{} chunks. Flags: C critical, P public, R reserved bit valid, S safe to copy",
+            self.chunks.len()
+        )
+    }
+}
+
+/// A message recovered by `decode --format json`. A binary payload has no `message`, only
+/// its file name and MIME type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct MessageReport<'a> {
+    pub chunk_type: &'a str,
+    pub keyword: Option<&'a str>,
+    pub message: Option<&'a str>,
+    pub filename: Option<&'a str>,
+    pub mime_type: Option<&'a str>,
+}
+
+/// The outcome of `validate --format json`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ValidationReport<'a> {
+    pub valid: bool,
+    pub violations: &'a [Violation],
+}
+
+impl<'a> ValidationReport<'a> {
+    pub fn new(violations: &'a [Violation]) -> ValidationReport<'a> {
+        ValidationReport {
+            valid: violations.is_empty(),
+            violations,
+        }
+    }
+}
+
+/// Describes the contents of IHDR, text and tIME chunks. Chunks of those types that can't
+/// be parsed are described by the parse error.
+fn summarize(chunk: &Chunk) -> Option<String> {
+    let summary = match *chunk.chunk_type() {
+        ChunkType::IHDR => Ihdr::try_from(chunk).map(|ihdr| {
+            format!(
+                "{}x{}, {}-bit {}, interlace {}",
+                ihdr.width(),
+                ihdr.height(),
+                ihdr.bit_depth(),
+                ihdr.color_type(),
+                ihdr.interlace_method()
+            )
+        }),
+        ChunkType::tIME => {
+            ModificationTime::try_from(chunk).map(|time| format!("modified {}", time))
+        }
+        _ => parse_text(chunk)?.map(|(keyword, _)| format!("keyword \"{}\"", keyword)),
+    };
+    Some(summary.unwrap_or_else(|error| error.to_string()))
+}
+
+/// Like `summarize`, copying only the chunks that have a summary.
+fn summarize_ref(chunk: &ChunkRef) -> Option<String> {
+    let chunk_type = *chunk.chunk_type();
+    if chunk_type == ChunkType::IHDR
+        || chunk_type == ChunkType::tIME
+        || is_text_chunk_type(&chunk_type)
+    {
+        summarize(&chunk.to_chunk())
+    } else {
+        None
+    }
+}
+
+/// Writes `data` as lines of hex bytes and printable ASCII, each prefixed with the
+/// file offset of its first byte.
+fn hex_dump(f: &mut fmt::Formatter<'_>, data: &[u8], offset: u64) -> fmt::Result {
+    for (line, bytes) in data.chunks(HEX_DUMP_WIDTH).enumerate() {
+        write!(f, "       {:08x} ", offset + (line * HEX_DUMP_WIDTH) as u64)?;
+        for column in 0..HEX_DUMP_WIDTH {
+            if column % 8 == 0 {
+                write!(f, " ")?;
+            }
+            match bytes.get(column) {
+                Some(byte) => write!(f, "{:02x} ", byte)?,
+                None => write!(f, "   ")?,
+            }
+        }
+        let ascii: String = bytes
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        writeln!(f, " |{}|", ascii)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+fn serialize_hex<S: Serializer>(data: &Option<&[u8]>, serializer: S) -> Result<S::Ok, S::Error> {
+    let hex: String = data
+        .unwrap_or_default()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    serializer.serialize_str(&hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::ihdr::{ColorType, InterlaceMethod};
+    use crate::text::TextChunk;
+    #[cfg(feature = "serde")]
+    use serde_json::json;
+    use std::str::FromStr;
+
+    fn png() -> Png {
+        let ihdr = Ihdr::new(2, 1, 8, ColorType::Rgb, InterlaceMethod::None).unwrap();
+        Png::from_chunks(vec![
+            ihdr.to_chunk(),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hi".to_vec()),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ])
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_png_report() {
+        let png = png();
+        let value = serde_json::to_value(PngReport::new(&png, false)).unwrap();
+
+        assert_eq!(value["ihdr"]["width"], 2);
+        assert_eq!(value["ihdr"]["color_type"], "Rgb");
+        assert_eq!(value["chunks"].as_array().unwrap().len(), 3);
+        assert_eq!(
+            value["chunks"][1],
+            json!({
+                "index": 1,
+                "offset": 33,
+                "chunk_type": "ruSt",
+                "length": 2,
+                "crc": png.chunks()[1].crc(),
+                "crc_valid": true,
+                "critical": false,
+                "public": false,
+                "reserved_bit_valid": true,
+                "safe_to_copy": true,
+                "summary": null,
+            })
+        );
+        assert_eq!(
+            value["chunks"][0]["summary"],
+            "2x1, 8-bit RGB, interlace None"
+        );
+
+        let value = serde_json::to_value(PngReport::new(&png, true)).unwrap();
+        assert_eq!(value["chunks"][1]["data"], "6869");
+        assert_eq!(value["chunks"][2]["data"], "");
+    }
+
+    #[test]
+    fn test_summaries() {
+        let text = TextChunk::new("Title", "Dice").unwrap().to_chunk();
+        assert_eq!(summarize(&text).unwrap(), "keyword \"Title\"");
+
+        let time = ModificationTime::new(2024, 2, 29, 13, 5, 0).unwrap();
+        assert_eq!(
+            summarize(&time.to_chunk()).unwrap(),
+            "modified 2024-02-29T13:05:00Z"
+        );
+
+        let bad_time = Chunk::new(ChunkType::from_str("tIME").unwrap(), vec![0; 7]);
+        assert!(summarize(&bad_time)
+            .unwrap()
+            .starts_with("invalid tIME chunk"));
+        assert_eq!(summarize(&png().chunks()[2]), None);
+    }
+
+    #[test]
+    fn test_table() {
+        let png = png();
+        let table = PngReport::new(&png, false).to_string();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("Index"));
+        assert!(lines[2].contains("ruSt"));
+        assert!(lines[2].contains("--RS"));
+        assert!(lines[3].contains("IEND"));
+        assert!(lines[3].contains("CPR-"));
+        assert!(lines[4].starts_with("3 chunks"));
+    }
+
+    #[test]
+    fn test_report_from_ref() {
+        let png = png();
+        let bytes = png.as_bytes();
+        let png_ref = PngRef::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(
+            PngReport::from_ref(&png_ref, true),
+            PngReport::new(&png, true)
+        );
+    }
+
+    #[test]
+    fn test_report_bad_crc() {
+        let mut bytes = png().as_bytes();
+        // The last byte of the ruSt chunk's CRC, which is followed by the 12 byte IEND
+        let crc = bytes.len() - 13;
+        bytes[crc] ^= 1;
+        let png_ref = PngRef::try_from(bytes.as_slice()).unwrap();
+
+        let report = PngReport::from_ref(&png_ref, false);
+        assert!(report.chunks[0].crc_valid);
+        assert!(!report.chunks[1].crc_valid);
+        assert_eq!(report.chunks[1].crc, png_ref.chunks()[1].stored_crc());
+        let table = report.to_string();
+        assert!(table.lines().nth(2).unwrap().ends_with("   bad CRC"));
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let png = png();
+        let table = PngReport::new(&png, true).to_string();
+        let dump: Vec<&str> = table.lines().filter(|line| line.contains('|')).collect();
+        assert_eq!(dump.len(), 2);
+        assert!(dump[0].contains("00000010  00 00 00 02 00 00 00 01  08 02 00 00 00"));
+        assert!(dump[1].trim_start().starts_with("00000029  68 69"));
+        assert!(dump[1].ends_with("|hi|"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_validation_report() {
+        let violations = [Violation::IendNotLast { index: 1 }];
+        let value = serde_json::to_value(ValidationReport::new(&violations)).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "valid": false,
+                "violations": [{ "kind": "iend_not_last", "index": 1 }],
+            })
+        );
+        let value = serde_json::to_value(ValidationReport::new(&[])).unwrap();
+        assert_eq!(value, json!({ "valid": true, "violations": [] }));
+    }
+}
@@ -0,0 +1,555 @@
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::error::{PngError, Result};
+
+/// The chunk types that hold textual metadata.
+pub const TEXT_CHUNK_TYPES: [ChunkType; 3] = [ChunkType::tEXt, ChunkType::zTXt, ChunkType::iTXt];
+
+/// The longest text a zTXt or iTXt chunk may decompress to. Compressed text is rejected
+/// beyond this, so a small chunk can't inflate into an unbounded amount of memory.
+pub const MAX_DECOMPRESSED_LENGTH: usize = 16 * 1024 * 1024;
+
+/// An uncompressed Latin-1 text chunk (tEXt). See the PNG Spec for more details
+/// http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.tEXt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    keyword: String,
+    text: String,
+}
+
+impl TextChunk {
+    /// Creates a `TextChunk`. Both the keyword and the text must be representable in
+    /// Latin-1, and the keyword must follow the spec's keyword rules.
+    pub fn new(keyword: &str, text: &str) -> Result<TextChunk> {
+        check_keyword(keyword)?;
+        check_latin1_text(text)?;
+        Ok(TextChunk {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    /// The keyword identifying this text
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    /// The text itself
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns a tEXt `Chunk` holding this text.
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = encode_latin1(&self.keyword);
+        data.push(0);
+        data.extend(encode_latin1(&self.text));
+        Chunk::new(ChunkType::tEXt, data)
+    }
+}
+
+impl TryFrom<&Chunk> for TextChunk {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        expect_chunk_type(chunk, ChunkType::tEXt)?;
+        let (keyword, text) = split_keyword(chunk.data())?;
+        if text.contains(&0) {
+            return Err(invalid_text("tEXt text contains a null byte"));
+        }
+        Ok(TextChunk {
+            keyword,
+            text: decode_latin1(text),
+        })
+    }
+}
+
+/// A zlib compressed Latin-1 text chunk (zTXt). See the PNG Spec for more details
+/// http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.zTXt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedTextChunk {
+    keyword: String,
+    text: String,
+}
+
+impl CompressedTextChunk {
+    /// Creates a `CompressedTextChunk`. Both the keyword and the text must be
+    /// representable in Latin-1, and the keyword must follow the spec's keyword rules.
+    /// The text can be at most `MAX_DECOMPRESSED_LENGTH` bytes.
+    pub fn new(keyword: &str, text: &str) -> Result<CompressedTextChunk> {
+        check_keyword(keyword)?;
+        check_latin1_text(text)?;
+        check_decompressed_length(text.chars().count())?;
+        Ok(CompressedTextChunk {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    /// The keyword identifying this text
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    /// The decompressed text
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns a zTXt `Chunk` holding this text, compressed with zlib.
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = encode_latin1(&self.keyword);
+        data.push(0);
+        data.push(COMPRESSION_METHOD_DEFLATE);
+        data.extend(compress(&encode_latin1(&self.text)));
+        Chunk::new(ChunkType::zTXt, data)
+    }
+}
+
+impl TryFrom<&Chunk> for CompressedTextChunk {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        expect_chunk_type(chunk, ChunkType::zTXt)?;
+        let (keyword, rest) = split_keyword(chunk.data())?;
+        let (&method, compressed) = rest
+            .split_first()
+            .ok_or_else(|| invalid_text("zTXt chunk is missing its compression method"))?;
+        check_compression_method(method)?;
+        Ok(CompressedTextChunk {
+            keyword,
+            text: decode_latin1(&decompress(compressed)?),
+        })
+    }
+}
+
+/// A UTF-8 text chunk with an optional language tag and translated keyword (iTXt).
+/// See the PNG Spec for more details
+/// https://www.w3.org/TR/png/#11iTXt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternationalTextChunk {
+    keyword: String,
+    compressed: bool,
+    language_tag: String,
+    translated_keyword: String,
+    text: String,
+}
+
+impl InternationalTextChunk {
+    /// Creates an `InternationalTextChunk` with no language tag or translated keyword.
+    /// The keyword must follow the spec's Latin-1 keyword rules, and compressed text can
+    /// be at most `MAX_DECOMPRESSED_LENGTH` bytes.
+    pub fn new(keyword: &str, text: &str, compressed: bool) -> Result<InternationalTextChunk> {
+        check_keyword(keyword)?;
+        if compressed {
+            check_decompressed_length(text.len())?;
+        }
+        Ok(InternationalTextChunk {
+            keyword: keyword.to_string(),
+            compressed,
+            language_tag: String::new(),
+            translated_keyword: String::new(),
+            text: text.to_string(),
+        })
+    }
+
+    /// Sets the RFC 3066 language tag and the keyword translated into that language.
+    pub fn with_language(mut self, language_tag: &str, translated_keyword: &str) -> Result<Self> {
+        check_language_tag(language_tag)?;
+        if translated_keyword.contains('\0') {
+            return Err(invalid_text("translated keyword contains a null byte"));
+        }
+        self.language_tag = language_tag.to_string();
+        self.translated_keyword = translated_keyword.to_string();
+        Ok(self)
+    }
+
+    /// The keyword identifying this text
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    /// Whether the text is stored zlib compressed
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// The language of the text, empty if unspecified
+    pub fn language_tag(&self) -> &str {
+        &self.language_tag
+    }
+
+    /// The keyword translated into the text's language, empty if unspecified
+    pub fn translated_keyword(&self) -> &str {
+        &self.translated_keyword
+    }
+
+    /// The decompressed text
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns an iTXt `Chunk` holding this text.
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = encode_latin1(&self.keyword);
+        data.push(0);
+        data.push(self.compressed as u8);
+        data.push(COMPRESSION_METHOD_DEFLATE);
+        data.extend(self.language_tag.as_bytes());
+        data.push(0);
+        data.extend(self.translated_keyword.as_bytes());
+        data.push(0);
+        if self.compressed {
+            data.extend(compress(self.text.as_bytes()));
+        } else {
+            data.extend(self.text.as_bytes());
+        }
+        Chunk::new(ChunkType::iTXt, data)
+    }
+}
+
+impl TryFrom<&Chunk> for InternationalTextChunk {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        expect_chunk_type(chunk, ChunkType::iTXt)?;
+        let (keyword, rest) = split_keyword(chunk.data())?;
+        if rest.len() < 2 {
+            return Err(invalid_text("iTXt chunk is missing its compression fields"));
+        }
+        let compressed = match rest[0] {
+            0 => false,
+            1 => true,
+            flag => {
+                return Err(invalid_text(&format!(
+                    "unknown iTXt compression flag {}",
+                    flag
+                )))
+            }
+        };
+        if compressed {
+            check_compression_method(rest[1])?;
+        }
+
+        let mut fields = rest[2..].splitn(3, |&byte| byte == 0);
+        let language_tag = fields.next().unwrap_or_default();
+        let translated_keyword = fields
+            .next()
+            .ok_or_else(|| invalid_text("iTXt chunk is missing its language tag terminator"))?;
+        let text = fields.next().ok_or_else(|| {
+            invalid_text("iTXt chunk is missing its translated keyword terminator")
+        })?;
+
+        let language_tag = std::str::from_utf8(language_tag)?;
+        check_language_tag(language_tag)?;
+        let text = if compressed {
+            String::from_utf8(decompress(text)?).map_err(|error| error.utf8_error())?
+        } else {
+            std::str::from_utf8(text)?.to_string()
+        };
+
+        Ok(InternationalTextChunk {
+            keyword,
+            compressed,
+            language_tag: language_tag.to_string(),
+            translated_keyword: std::str::from_utf8(translated_keyword)?.to_string(),
+            text,
+        })
+    }
+}
+
+/// Whether `chunk_type` is one of the textual metadata chunk types.
+pub fn is_text_chunk_type(chunk_type: &ChunkType) -> bool {
+    TEXT_CHUNK_TYPES.contains(chunk_type)
+}
+
+/// Builds a text chunk of the given type (tEXt, zTXt or uncompressed iTXt) holding
+/// `text` under `keyword`.
+pub fn text_chunk(chunk_type: ChunkType, keyword: &str, text: &str) -> Result<Chunk> {
+    match chunk_type {
+        ChunkType::tEXt => Ok(TextChunk::new(keyword, text)?.to_chunk()),
+        ChunkType::zTXt => Ok(CompressedTextChunk::new(keyword, text)?.to_chunk()),
+        ChunkType::iTXt => Ok(InternationalTextChunk::new(keyword, text, false)?.to_chunk()),
+        other => Err(invalid_text(&format!(
+            "{} is not a text chunk type, use one of tEXt, zTXt, iTXt",
+            other
+        ))),
+    }
+}
+
+/// Returns the keyword and text of any tEXt, zTXt or iTXt chunk, or `None` if `chunk`
+/// is not a text chunk.
+pub fn parse_text(chunk: &Chunk) -> Option<Result<(String, String)>> {
+    let parsed = match *chunk.chunk_type() {
+        ChunkType::tEXt => TextChunk::try_from(chunk).map(|text| (text.keyword, text.text)),
+        ChunkType::zTXt => {
+            CompressedTextChunk::try_from(chunk).map(|text| (text.keyword, text.text))
+        }
+        ChunkType::iTXt => {
+            InternationalTextChunk::try_from(chunk).map(|text| (text.keyword, text.text))
+        }
+        _ => return None,
+    };
+    Some(parsed)
+}
+
+const COMPRESSION_METHOD_DEFLATE: u8 = 0;
+
+fn invalid_text(reason: &str) -> PngError {
+    PngError::InvalidText(reason.to_string())
+}
+
+fn expect_chunk_type(chunk: &Chunk, expected: ChunkType) -> Result<()> {
+    if *chunk.chunk_type() != expected {
+        return Err(invalid_text(&format!(
+            "expected a {} chunk, found {}",
+            expected,
+            chunk.chunk_type()
+        )));
+    }
+    Ok(())
+}
+
+/// Splits chunk data at the null separator following the keyword.
+fn split_keyword(data: &[u8]) -> Result<(String, &[u8])> {
+    let separator = data
+        .iter()
+        .position(|&byte| byte == 0)
+        .ok_or_else(|| invalid_text("missing null separator after keyword"))?;
+    let keyword = decode_latin1(&data[..separator]);
+    check_keyword(&keyword)?;
+    Ok((keyword, &data[separator + 1..]))
+}
+
+/// Keywords are 1-79 printable Latin-1 characters with no leading, trailing or
+/// consecutive spaces.
+fn check_keyword(keyword: &str) -> Result<()> {
+    let length = keyword.chars().count();
+    if !(1..=79).contains(&length) {
+        return Err(invalid_text(&format!(
+            "keyword must be 1-79 characters, found {}",
+            length
+        )));
+    }
+    let printable = keyword
+        .chars()
+        .all(|c| matches!(c as u32, 32..=126 | 161..=255));
+    if !printable {
+        return Err(invalid_text(&format!(
+            "keyword \"{}\" contains characters outside printable Latin-1",
+            keyword
+        )));
+    }
+    if keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+        return Err(invalid_text(&format!(
+            "keyword \"{}\" has leading, trailing or consecutive spaces",
+            keyword
+        )));
+    }
+    Ok(())
+}
+
+fn check_latin1_text(text: &str) -> Result<()> {
+    if let Some(c) = text.chars().find(|&c| c == '\0' || c as u32 > 255) {
+        return Err(invalid_text(&format!(
+            "character {:?} can't be stored in Latin-1 text, use iTXt instead",
+            c
+        )));
+    }
+    Ok(())
+}
+
+/// Language tags are hyphen separated words of ASCII letters and digits.
+fn check_language_tag(language_tag: &str) -> Result<()> {
+    let valid = language_tag
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if !valid {
+        return Err(invalid_text(&format!(
+            "invalid language tag \"{}\"",
+            language_tag
+        )));
+    }
+    Ok(())
+}
+
+fn check_compression_method(method: u8) -> Result<()> {
+    if method != COMPRESSION_METHOD_DEFLATE {
+        return Err(invalid_text(&format!(
+            "unknown compression method {}",
+            method
+        )));
+    }
+    Ok(())
+}
+
+/// Latin-1 maps every byte directly to the Unicode code point of the same value.
+fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| byte as char).collect()
+}
+
+/// Callers must have checked that every character is at most U+00FF.
+fn encode_latin1(text: &str) -> Vec<u8> {
+    text.chars().map(|c| c as u8).collect()
+}
+
+fn compress(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(bytes)
+        .expect("writing to a Vec can't fail");
+    encoder.finish().expect("writing to a Vec can't fail")
+}
+
+fn decompress(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    ZlibDecoder::new(bytes)
+        .take(MAX_DECOMPRESSED_LENGTH as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|error| invalid_text(&format!("invalid zlib stream: {}", error)))?;
+    check_decompressed_length(decompressed.len())?;
+    Ok(decompressed)
+}
+
+fn check_decompressed_length(length: usize) -> Result<()> {
+    if length > MAX_DECOMPRESSED_LENGTH {
+        return Err(invalid_text(&format!(
+            "compressed text is longer than {} bytes",
+            MAX_DECOMPRESSED_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_text_round_trip() {
+        let text = TextChunk::new("Comment", "Caf\u{e9} au lait").unwrap();
+        let chunk = text.to_chunk();
+        assert_eq!(chunk.chunk_type().to_string(), "tEXt");
+        assert_eq!(&chunk.data()[..8], b"Comment\0");
+        // é is a single Latin-1 byte
+        assert_eq!(chunk.data()[11], 0xe9);
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_text_rejects_non_latin1() {
+        assert!(TextChunk::new("Comment", "snowman \u{2603}").is_err());
+        assert!(TextChunk::new("Comment", "null\0byte").is_err());
+    }
+
+    #[test]
+    fn test_keyword_rules() {
+        assert!(TextChunk::new("Title", "").is_ok());
+        assert!(TextChunk::new("", "text").is_err());
+        assert!(TextChunk::new(&"k".repeat(80), "text").is_err());
+        assert!(TextChunk::new(" Title", "text").is_err());
+        assert!(TextChunk::new("Title ", "text").is_err());
+        assert!(TextChunk::new("Two  Spaces", "text").is_err());
+        assert!(TextChunk::new("Tab\tKey", "text").is_err());
+    }
+
+    #[test]
+    fn test_compressed_text_round_trip() {
+        let message = "compress me ".repeat(50);
+        let text = CompressedTextChunk::new("Description", &message).unwrap();
+        let chunk = text.to_chunk();
+        assert_eq!(chunk.chunk_type().to_string(), "zTXt");
+        assert!(chunk.data().len() < message.len());
+        assert_eq!(CompressedTextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_international_text_round_trip() {
+        for compressed in [false, true] {
+            let text = InternationalTextChunk::new("Title", "\u{65e5}\u{672c}", compressed)
+                .unwrap()
+                .with_language("ja-JP", "\u{984c}\u{540d}")
+                .unwrap();
+            let chunk = text.to_chunk();
+            assert_eq!(chunk.chunk_type().to_string(), "iTXt");
+            let parsed = InternationalTextChunk::try_from(&chunk).unwrap();
+            assert_eq!(parsed, text);
+            assert_eq!(parsed.language_tag(), "ja-JP");
+            assert_eq!(parsed.translated_keyword(), "\u{984c}\u{540d}");
+        }
+    }
+
+    #[test]
+    fn test_invalid_language_tag() {
+        let text = InternationalTextChunk::new("Title", "text", false).unwrap();
+        assert!(text.with_language("en US", "").is_err());
+    }
+
+    #[test]
+    fn test_missing_separator() {
+        let chunk = Chunk::new(ChunkType::tEXt, b"no separator".to_vec());
+        assert!(matches!(
+            TextChunk::try_from(&chunk),
+            Err(PngError::InvalidText(_))
+        ));
+    }
+
+    #[test]
+    fn test_decompression_is_bounded() {
+        let zeros = compress(&vec![b'0'; MAX_DECOMPRESSED_LENGTH + 1]);
+        let mut data = b"Comment\0\0".to_vec();
+        data.extend(&zeros);
+        let chunk = Chunk::new(ChunkType::zTXt, data);
+        assert!(matches!(
+            CompressedTextChunk::try_from(&chunk),
+            Err(PngError::InvalidText(_))
+        ));
+
+        let mut data = b"Comment\0\x01\0\0\0".to_vec();
+        data.extend(&zeros);
+        let chunk = Chunk::new(ChunkType::iTXt, data);
+        assert!(matches!(
+            InternationalTextChunk::try_from(&chunk),
+            Err(PngError::InvalidText(_))
+        ));
+
+        let text = "0".repeat(MAX_DECOMPRESSED_LENGTH + 1);
+        assert!(CompressedTextChunk::new("Comment", &text).is_err());
+        assert!(InternationalTextChunk::new("Comment", &text, true).is_err());
+        assert!(InternationalTextChunk::new("Comment", &text, false).is_ok());
+    }
+
+    #[test]
+    fn test_parse_text() {
+        let chunk = CompressedTextChunk::new("Author", "Ferris")
+            .unwrap()
+            .to_chunk();
+        let (keyword, text) = parse_text(&chunk).unwrap().unwrap();
+        assert_eq!(keyword, "Author");
+        assert_eq!(text, "Ferris");
+
+        let other = Chunk::new(ChunkType::from_str("RuSt").unwrap(), Vec::new());
+        assert!(parse_text(&other).is_none());
+    }
+
+    #[test]
+    fn test_text_chunk() {
+        for chunk_type in TEXT_CHUNK_TYPES {
+            let chunk = text_chunk(chunk_type, "Comment", "hidden").unwrap();
+            assert_eq!(*chunk.chunk_type(), chunk_type);
+            let (keyword, text) = parse_text(&chunk).unwrap().unwrap();
+            assert_eq!(keyword, "Comment");
+            assert_eq!(text, "hidden");
+        }
+        let other = ChunkType::from_str("RuSt").unwrap();
+        assert!(text_chunk(other, "Comment", "hidden").is_err());
+    }
+}
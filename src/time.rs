@@ -0,0 +1,198 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::error::{PngError, Result};
+
+/// The image last-modification time chunk (tIME), always in UTC. See the PNG Spec
+/// http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.tIME
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModificationTime {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+
+impl ModificationTime {
+    /// The length of the tIME chunk data in bytes.
+    pub const LENGTH: usize = 7;
+
+    /// Creates a `ModificationTime`, failing if a field is out of range or the day is past
+    /// the end of the month. A `second` of 60 is allowed for leap seconds.
+    pub fn new(
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+    ) -> Result<ModificationTime> {
+        let fields = [
+            ("month", month, 1..=12),
+            ("day", day, 1..=31),
+            ("hour", hour, 0..=23),
+            ("minute", minute, 0..=59),
+            ("second", second, 0..=60),
+        ];
+        for (name, value, range) in fields {
+            if !range.contains(&value) {
+                return Err(PngError::InvalidTime(format!(
+                    "{} {} is out of range",
+                    name, value
+                )));
+            }
+        }
+        if day > days_in_month(year, month) {
+            return Err(PngError::InvalidTime(format!(
+                "day {} is out of range for {}-{:02}",
+                day, year, month
+            )));
+        }
+        Ok(ModificationTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        })
+    }
+
+    /// The complete year, e.g. 1995
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// The month, from 1 to 12
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    /// The day of the month, from 1 to 31
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// The hour, from 0 to 23
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    /// The minute, from 0 to 59
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    /// The second, from 0 to 60
+    pub fn second(&self) -> u8 {
+        self.second
+    }
+
+    /// Returns a tIME `Chunk` holding this `ModificationTime`.
+    pub fn to_chunk(self) -> Chunk {
+        let mut data = Vec::with_capacity(ModificationTime::LENGTH);
+        data.extend_from_slice(&self.year.to_be_bytes());
+        data.extend_from_slice(&[self.month, self.day, self.hour, self.minute, self.second]);
+        Chunk::new(ChunkType::tIME, data)
+    }
+}
+
+impl TryFrom<&Chunk> for ModificationTime {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if *chunk.chunk_type() != ChunkType::tIME {
+            return Err(PngError::InvalidTime(format!(
+                "expected a tIME chunk, found {}",
+                chunk.chunk_type()
+            )));
+        }
+        let data = chunk.data();
+        if data.len() != ModificationTime::LENGTH {
+            return Err(PngError::InvalidTime(format!(
+                "expected {} bytes of data, found {}",
+                ModificationTime::LENGTH,
+                data.len()
+            )));
+        }
+        ModificationTime::new(
+            u16::from_be_bytes([data[0], data[1]]),
+            data[2],
+            data[3],
+            data[4],
+            data[5],
+            data[6],
+        )
+    }
+}
+
+impl fmt::Display for ModificationTime {
+    /// Formats the time as RFC 3339, e.g. `2024-02-29T13:05:00Z`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// The number of days in `month` of `year` in the Gregorian calendar.
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_round_trip() {
+        let time = ModificationTime::new(2024, 2, 29, 13, 5, 0).unwrap();
+        let chunk = time.to_chunk();
+        assert_eq!(chunk.data(), &[0x07, 0xe8, 2, 29, 13, 5, 0]);
+        assert_eq!(ModificationTime::try_from(&chunk).unwrap(), time);
+        assert_eq!(time.to_string(), "2024-02-29T13:05:00Z");
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        assert!(matches!(
+            ModificationTime::new(2024, 13, 1, 0, 0, 0),
+            Err(PngError::InvalidTime(_))
+        ));
+        assert!(ModificationTime::new(2024, 1, 0, 0, 0, 0).is_err());
+        assert!(ModificationTime::new(2016, 12, 31, 23, 59, 60).is_ok());
+    }
+
+    #[test]
+    fn test_day_past_end_of_month() {
+        assert!(matches!(
+            ModificationTime::new(2023, 2, 31, 0, 0, 0),
+            Err(PngError::InvalidTime(_))
+        ));
+        assert!(ModificationTime::new(2023, 2, 29, 0, 0, 0).is_err());
+        assert!(ModificationTime::new(2023, 4, 31, 0, 0, 0).is_err());
+        assert!(ModificationTime::new(2000, 2, 29, 0, 0, 0).is_ok());
+        assert!(ModificationTime::new(1900, 2, 29, 0, 0, 0).is_err());
+        assert!(ModificationTime::new(2023, 12, 31, 0, 0, 0).is_ok());
+    }
+
+    #[test]
+    fn test_wrong_length() {
+        let chunk = Chunk::new(ChunkType::from_str("tIME").unwrap(), vec![0x07, 0xe8, 1]);
+        assert!(ModificationTime::try_from(&chunk).is_err());
+    }
+}
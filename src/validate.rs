@@ -0,0 +1,665 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::apng::{AnimationControl, FrameControl, FrameData};
+use crate::chunk::Chunk;
+use crate::chunk_ref::ChunkRef;
+use crate::chunk_type::ChunkType;
+use crate::error::PngError;
+use crate::ihdr::{ColorType, Ihdr};
+
+/// Chunks that must appear before every chunk in the paired list, per PNG 1.2 section 4.3.
+const MUST_PRECEDE: &[(ChunkType, &[ChunkType])] = &[
+    (ChunkType::PLTE, &[ChunkType::IDAT]),
+    (ChunkType::cHRM, &[ChunkType::PLTE, ChunkType::IDAT]),
+    (ChunkType::gAMA, &[ChunkType::PLTE, ChunkType::IDAT]),
+    (ChunkType::iCCP, &[ChunkType::PLTE, ChunkType::IDAT]),
+    (ChunkType::sBIT, &[ChunkType::PLTE, ChunkType::IDAT]),
+    (ChunkType::sRGB, &[ChunkType::PLTE, ChunkType::IDAT]),
+    (ChunkType::bKGD, &[ChunkType::IDAT]),
+    (ChunkType::hIST, &[ChunkType::IDAT]),
+    (ChunkType::tRNS, &[ChunkType::IDAT]),
+    (ChunkType::pHYs, &[ChunkType::IDAT]),
+    (ChunkType::sPLT, &[ChunkType::IDAT]),
+    (ChunkType::acTL, &[ChunkType::IDAT]),
+];
+
+/// Chunks that must appear after PLTE when a PLTE chunk is present.
+const MUST_FOLLOW_PLTE: &[ChunkType] = &[ChunkType::bKGD, ChunkType::hIST, ChunkType::tRNS];
+
+/// Chunks that may appear at most once.
+const UNIQUE: &[ChunkType] = &[
+    ChunkType::IHDR,
+    ChunkType::PLTE,
+    ChunkType::IEND,
+    ChunkType::cHRM,
+    ChunkType::gAMA,
+    ChunkType::iCCP,
+    ChunkType::sBIT,
+    ChunkType::sRGB,
+    ChunkType::bKGD,
+    ChunkType::hIST,
+    ChunkType::tRNS,
+    ChunkType::pHYs,
+    ChunkType::tIME,
+    ChunkType::acTL,
+];
+
+/// Critical chunks defined by the PNG spec. Any other critical chunk can't be understood
+/// by a decoder.
+const KNOWN_CRITICAL: &[ChunkType] = &[
+    ChunkType::IHDR,
+    ChunkType::PLTE,
+    ChunkType::IDAT,
+    ChunkType::IEND,
+];
+
+/// A single violation of the PNG chunk ordering rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize),
+    serde(tag = "kind", rename_all = "snake_case")
+)]
+pub enum Violation {
+    /// A required chunk is absent.
+    MissingChunk { chunk_type: String },
+    /// IHDR exists but is not the first chunk.
+    IhdrNotFirst { index: usize },
+    /// IEND exists but is not the last chunk.
+    IendNotLast { index: usize },
+    /// A non-IDAT chunk sits between two IDAT chunks.
+    NonConsecutiveIdat { index: usize },
+    /// A chunk appears after a chunk it is required to precede.
+    MustPrecede {
+        chunk_type: String,
+        index: usize,
+        other: String,
+    },
+    /// A chunk appears before a chunk it is required to follow.
+    MustFollow {
+        chunk_type: String,
+        index: usize,
+        other: String,
+    },
+    /// A chunk that may only appear once is repeated.
+    Duplicate { chunk_type: String, index: usize },
+    /// Two chunks that must not both be present were found.
+    Conflicting { first: String, second: String },
+    /// A critical chunk that isn't defined by the spec.
+    UnknownCriticalChunk { chunk_type: String, index: usize },
+    /// An IHDR, acTL, fcTL or fdAT chunk can't be parsed.
+    MalformedChunk {
+        chunk_type: String,
+        index: usize,
+        reason: String,
+    },
+    /// An fcTL or fdAT sequence number doesn't continue the sequence.
+    SequenceOutOfOrder {
+        chunk_type: String,
+        index: usize,
+        expected: u32,
+        found: u32,
+    },
+    /// acTL declares a different number of frames than there are fcTL chunks.
+    FrameCountMismatch { declared: u32, found: u32 },
+    /// The region of the frame whose fcTL is at `index` extends past the image.
+    FrameOutOfBounds {
+        index: usize,
+        frame: (u32, u32, u32, u32),
+        image: (u32, u32),
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::MissingChunk { chunk_type } => write!(f, "missing {} chunk", chunk_type),
+            Violation::IhdrNotFirst { index } => {
+                write!(f, "IHDR must be the first chunk but is chunk {}", index)
+            }
+            Violation::IendNotLast { index } => {
+                write!(f, "IEND must be the last chunk but is chunk {}", index)
+            }
+            Violation::NonConsecutiveIdat { index } => {
+                write!(f, "chunk {} interrupts the IDAT sequence", index)
+            }
+            Violation::MustPrecede {
+                chunk_type,
+                index,
+                other,
+            } => write!(
+                f,
+                "{} (chunk {}) must come before {}",
+                chunk_type, index, other
+            ),
+            Violation::MustFollow {
+                chunk_type,
+                index,
+                other,
+            } => write!(
+                f,
+                "{} (chunk {}) must come after {}",
+                chunk_type, index, other
+            ),
+            Violation::Duplicate { chunk_type, index } => {
+                write!(f, "duplicate {} chunk at chunk {}", chunk_type, index)
+            }
+            Violation::Conflicting { first, second } => {
+                write!(f, "{} and {} must not both be present", first, second)
+            }
+            Violation::UnknownCriticalChunk { chunk_type, index } => {
+                write!(
+                    f,
+                    "unknown critical chunk {} at chunk {}",
+                    chunk_type, index
+                )
+            }
+            Violation::MalformedChunk {
+                chunk_type,
+                index,
+                reason,
+            } => write!(
+                f,
+                "{} (chunk {}) is malformed: {}",
+                chunk_type, index, reason
+            ),
+            Violation::SequenceOutOfOrder {
+                chunk_type,
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} (chunk {}) has sequence number {} but {} was expected",
+                chunk_type, index, found, expected
+            ),
+            Violation::FrameCountMismatch { declared, found } => write!(
+                f,
+                "acTL declares {} frames but {} fcTL chunks were found",
+                declared, found
+            ),
+            Violation::FrameOutOfBounds {
+                index,
+                frame: (width, height, x_offset, y_offset),
+                image: (image_width, image_height),
+            } => write!(
+                f,
+                "frame at chunk {} ({}x{} at {},{}) extends past the {}x{} image",
+                index, width, height, x_offset, y_offset, image_width, image_height
+            ),
+        }
+    }
+}
+
+/// The chunk types whose contents are checked, rather than just their position.
+const CHECKED_CONTENTS: [ChunkType; 4] = [
+    ChunkType::IHDR,
+    ChunkType::acTL,
+    ChunkType::fcTL,
+    ChunkType::fdAT,
+];
+
+/// Checks `chunks` against the PNG 1.2 chunk ordering rules and returns every violation
+/// found, in file order where possible.
+pub fn validate_chunks(chunks: &[Chunk]) -> Vec<Violation> {
+    let chunk_types: Vec<ChunkType> = chunks.iter().map(|chunk| *chunk.chunk_type()).collect();
+    let checked: Vec<(usize, &Chunk)> = chunks
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| CHECKED_CONTENTS.contains(chunk.chunk_type()))
+        .collect();
+    validate(&chunk_types, &checked)
+}
+
+/// Like `validate_chunks`, but for borrowed chunks. Only the IHDR and APNG chunks are
+/// copied, so the image data is never read.
+pub fn validate_chunk_refs(chunks: &[ChunkRef]) -> Vec<Violation> {
+    let chunk_types: Vec<ChunkType> = chunks.iter().map(|chunk| *chunk.chunk_type()).collect();
+    let copies: Vec<(usize, Chunk)> = chunks
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| CHECKED_CONTENTS.contains(chunk.chunk_type()))
+        .map(|(index, chunk)| (index, chunk.to_chunk()))
+        .collect();
+    let checked: Vec<(usize, &Chunk)> = copies
+        .iter()
+        .map(|(index, chunk)| (*index, chunk))
+        .collect();
+    validate(&chunk_types, &checked)
+}
+
+/// Checks the order of `chunk_types`, and the contents of the `CHECKED_CONTENTS` chunks
+/// in `checked` along with their indices.
+fn validate(chunk_types: &[ChunkType], checked: &[(usize, &Chunk)]) -> Vec<Violation> {
+    let first = |wanted: ChunkType| chunk_types.iter().position(|&t| t == wanted);
+    let last = |wanted: ChunkType| chunk_types.iter().rposition(|&t| t == wanted);
+    let mut violations = Vec::new();
+
+    match first(ChunkType::IHDR) {
+        None => violations.push(Violation::MissingChunk {
+            chunk_type: ChunkType::IHDR.to_string(),
+        }),
+        Some(index) if index != 0 => violations.push(Violation::IhdrNotFirst { index }),
+        Some(_) => (),
+    }
+
+    if let Some(&(index, chunk)) = checked
+        .iter()
+        .find(|(_, chunk)| *chunk.chunk_type() == ChunkType::IHDR)
+    {
+        match Ihdr::try_from(chunk) {
+            Ok(ihdr) if ihdr.color_type() == ColorType::Indexed => {
+                if first(ChunkType::PLTE).is_none() {
+                    violations.push(Violation::MissingChunk {
+                        chunk_type: ChunkType::PLTE.to_string(),
+                    });
+                }
+            }
+            Ok(_) => (),
+            Err(error) => violations.push(Violation::MalformedChunk {
+                chunk_type: ChunkType::IHDR.to_string(),
+                index,
+                reason: error.to_string(),
+            }),
+        }
+    }
+
+    match (first(ChunkType::IDAT), last(ChunkType::IDAT)) {
+        (Some(start), Some(end)) => {
+            if let Some(index) = (start..=end).find(|&i| chunk_types[i] != ChunkType::IDAT) {
+                violations.push(Violation::NonConsecutiveIdat { index });
+            }
+        }
+        _ => violations.push(Violation::MissingChunk {
+            chunk_type: ChunkType::IDAT.to_string(),
+        }),
+    }
+
+    for (index, &chunk_type) in chunk_types.iter().enumerate() {
+        if let Some((_, others)) = MUST_PRECEDE.iter().find(|&&(t, _)| t == chunk_type) {
+            for &other in others.iter() {
+                if first(other).is_some_and(|other_index| other_index < index) {
+                    violations.push(Violation::MustPrecede {
+                        chunk_type: chunk_type.to_string(),
+                        index,
+                        other: other.to_string(),
+                    });
+                }
+            }
+        }
+
+        if MUST_FOLLOW_PLTE.contains(&chunk_type)
+            && first(ChunkType::PLTE).is_some_and(|plte| index < plte)
+        {
+            violations.push(Violation::MustFollow {
+                chunk_type: chunk_type.to_string(),
+                index,
+                other: ChunkType::PLTE.to_string(),
+            });
+        }
+
+        if UNIQUE.contains(&chunk_type) && first(chunk_type) != Some(index) {
+            violations.push(Violation::Duplicate {
+                chunk_type: chunk_type.to_string(),
+                index,
+            });
+        }
+
+        if chunk_type.is_critical() && !KNOWN_CRITICAL.contains(&chunk_type) {
+            violations.push(Violation::UnknownCriticalChunk {
+                chunk_type: chunk_type.to_string(),
+                index,
+            });
+        }
+    }
+
+    if first(ChunkType::iCCP).is_some() && first(ChunkType::sRGB).is_some() {
+        violations.push(Violation::Conflicting {
+            first: ChunkType::iCCP.to_string(),
+            second: ChunkType::sRGB.to_string(),
+        });
+    }
+
+    if first(ChunkType::acTL).is_some() || first(ChunkType::fcTL).is_some() {
+        validate_frames(checked, &mut violations);
+    }
+
+    match first(ChunkType::IEND) {
+        None => violations.push(Violation::MissingChunk {
+            chunk_type: ChunkType::IEND.to_string(),
+        }),
+        Some(index) if index != chunk_types.len() - 1 => {
+            violations.push(Violation::IendNotLast { index })
+        }
+        Some(_) => (),
+    }
+
+    violations
+}
+
+/// Checks the APNG chunks: they must parse, their sequence numbers must count up from 0,
+/// acTL must declare the right number of frames and every frame must fit in the image.
+fn validate_frames(chunks: &[(usize, &Chunk)], violations: &mut Vec<Violation>) {
+    let image = chunks
+        .iter()
+        .find_map(|(_, chunk)| Ihdr::try_from(*chunk).ok())
+        .map(|ihdr| (ihdr.width(), ihdr.height()));
+    let malformed = |index: usize, chunk: &Chunk, error: PngError| Violation::MalformedChunk {
+        chunk_type: chunk.chunk_type().to_string(),
+        index,
+        reason: error.to_string(),
+    };
+
+    let mut declared = None;
+    let mut found = 0;
+    let mut expected = 0;
+    for &(index, chunk) in chunks {
+        let sequence_number = match *chunk.chunk_type() {
+            ChunkType::acTL => {
+                match AnimationControl::try_from(chunk) {
+                    Ok(control) => declared = declared.or(Some(control.num_frames())),
+                    Err(error) => violations.push(malformed(index, chunk, error)),
+                }
+                continue;
+            }
+            ChunkType::fcTL => {
+                found += 1;
+                let control = match FrameControl::try_from(chunk) {
+                    Ok(control) => control,
+                    Err(error) => {
+                        violations.push(malformed(index, chunk, error));
+                        continue;
+                    }
+                };
+                if let Some((image_width, image_height)) = image {
+                    if control.x_offset() as u64 + control.width() as u64 > image_width as u64
+                        || control.y_offset() as u64 + control.height() as u64 > image_height as u64
+                    {
+                        violations.push(Violation::FrameOutOfBounds {
+                            index,
+                            frame: (
+                                control.width(),
+                                control.height(),
+                                control.x_offset(),
+                                control.y_offset(),
+                            ),
+                            image: (image_width, image_height),
+                        });
+                    }
+                }
+                control.sequence_number()
+            }
+            ChunkType::fdAT => match FrameData::try_from(chunk) {
+                Ok(frame_data) => frame_data.sequence_number(),
+                Err(error) => {
+                    violations.push(malformed(index, chunk, error));
+                    continue;
+                }
+            },
+            _ => continue,
+        };
+
+        if sequence_number != expected {
+            violations.push(Violation::SequenceOutOfOrder {
+                chunk_type: chunk.chunk_type().to_string(),
+                index,
+                expected,
+                found: sequence_number,
+            });
+        }
+        expected = sequence_number.wrapping_add(1);
+    }
+
+    if let Some(declared) = declared {
+        if declared != found {
+            violations.push(Violation::FrameCountMismatch { declared, found });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::InterlaceMethod;
+    use crate::png::Png;
+    use crate::png_ref::PngRef;
+    use std::str::FromStr;
+
+    fn chunks(types: &[&str]) -> Vec<Chunk> {
+        types
+            .iter()
+            .map(|t| match *t {
+                "IHDR" => Ihdr::new(1, 1, 8, ColorType::Rgb, InterlaceMethod::None)
+                    .unwrap()
+                    .to_chunk(),
+                t => Chunk::new(ChunkType::from_str(t).unwrap(), Vec::new()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_ordering() {
+        let chunks = chunks(&[
+            "IHDR", "gAMA", "PLTE", "tRNS", "pHYs", "IDAT", "IDAT", "tEXt", "IEND",
+        ]);
+        assert!(validate_chunks(&chunks).is_empty());
+    }
+
+    #[test]
+    fn test_missing_required_chunks() {
+        let violations = validate_chunks(&chunks(&["tEXt"]));
+        for chunk_type in ["IHDR", "IDAT", "IEND"] {
+            assert!(violations.contains(&Violation::MissingChunk {
+                chunk_type: chunk_type.to_string()
+            }));
+        }
+    }
+
+    #[test]
+    fn test_ihdr_first_and_iend_last() {
+        let violations = validate_chunks(&chunks(&["tEXt", "IHDR", "IDAT", "IEND", "ruSt"]));
+        assert!(violations.contains(&Violation::IhdrNotFirst { index: 1 }));
+        assert!(violations.contains(&Violation::IendNotLast { index: 3 }));
+    }
+
+    #[test]
+    fn test_non_consecutive_idat() {
+        let violations = validate_chunks(&chunks(&["IHDR", "IDAT", "tEXt", "IDAT", "IEND"]));
+        assert_eq!(violations, vec![Violation::NonConsecutiveIdat { index: 2 }]);
+    }
+
+    #[test]
+    fn test_plte_after_idat() {
+        let violations = validate_chunks(&chunks(&["IHDR", "IDAT", "PLTE", "IEND"]));
+        assert_eq!(
+            violations,
+            vec![Violation::MustPrecede {
+                chunk_type: "PLTE".to_string(),
+                index: 2,
+                other: "IDAT".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_gama_after_plte() {
+        let violations = validate_chunks(&chunks(&["IHDR", "PLTE", "gAMA", "IDAT", "IEND"]));
+        assert_eq!(
+            violations,
+            vec![Violation::MustPrecede {
+                chunk_type: "gAMA".to_string(),
+                index: 2,
+                other: "PLTE".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_trns_before_plte() {
+        let violations = validate_chunks(&chunks(&["IHDR", "tRNS", "PLTE", "IDAT", "IEND"]));
+        assert_eq!(
+            violations,
+            vec![Violation::MustFollow {
+                chunk_type: "tRNS".to_string(),
+                index: 1,
+                other: "PLTE".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_duplicates_and_conflicts() {
+        let violations = validate_chunks(&chunks(&[
+            "IHDR", "gAMA", "gAMA", "iCCP", "sRGB", "IDAT", "tEXt", "tEXt", "IEND",
+        ]));
+        assert_eq!(
+            violations,
+            vec![
+                Violation::Duplicate {
+                    chunk_type: "gAMA".to_string(),
+                    index: 2
+                },
+                Violation::Conflicting {
+                    first: "iCCP".to_string(),
+                    second: "sRGB".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_critical_chunk() {
+        let violations = validate_chunks(&chunks(&["IHDR", "RuSt", "IDAT", "IEND"]));
+        assert_eq!(
+            violations,
+            vec![Violation::UnknownCriticalChunk {
+                chunk_type: "RuSt".to_string(),
+                index: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn test_malformed_ihdr() {
+        let mut chunks = chunks(&["IHDR", "IDAT", "IEND"]);
+        // An 8-bit image can't have a bit depth of 3
+        let mut data = chunks[0].data().to_vec();
+        data[8] = 3;
+        chunks[0] = Chunk::new(ChunkType::IHDR, data);
+        let violations = validate_chunks(&chunks);
+        assert_eq!(violations.len(), 1);
+        assert!(matches!(
+            &violations[0],
+            Violation::MalformedChunk { chunk_type, index: 0, .. } if chunk_type == "IHDR"
+        ));
+    }
+
+    #[test]
+    fn test_indexed_without_plte() {
+        let ihdr = Ihdr::new(1, 1, 8, ColorType::Indexed, InterlaceMethod::None).unwrap();
+        let mut chunks = chunks(&["IDAT", "IEND"]);
+        chunks.insert(0, ihdr.to_chunk());
+        assert_eq!(
+            validate_chunks(&chunks),
+            vec![Violation::MissingChunk {
+                chunk_type: "PLTE".to_string()
+            }]
+        );
+
+        chunks.insert(1, Chunk::new(ChunkType::PLTE, vec![0, 0, 0]));
+        assert!(validate_chunks(&chunks).is_empty());
+    }
+
+    fn animation(frames: &[(u32, u32, u32, u32)]) -> Vec<Chunk> {
+        use crate::apng::{BlendOp, DisposeOp};
+        let ihdr = Ihdr::new(10, 10, 8, ColorType::Rgba, InterlaceMethod::None).unwrap();
+        let mut chunks = vec![
+            ihdr.to_chunk(),
+            AnimationControl::new(frames.len() as u32, 0)
+                .unwrap()
+                .to_chunk(),
+        ];
+        let mut sequence_number = 0;
+        for (n, &(width, height, x_offset, y_offset)) in frames.iter().enumerate() {
+            let control = FrameControl::new(
+                sequence_number,
+                width,
+                height,
+                x_offset,
+                y_offset,
+                1,
+                10,
+                DisposeOp::None,
+                BlendOp::Source,
+            )
+            .unwrap();
+            chunks.push(control.to_chunk());
+            sequence_number += 1;
+            if n == 0 {
+                chunks.push(Chunk::new(ChunkType::from_str("IDAT").unwrap(), Vec::new()));
+            } else {
+                chunks.push(FrameData::new(sequence_number, Vec::new()).to_chunk());
+                sequence_number += 1;
+            }
+        }
+        chunks.push(Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()));
+        chunks
+    }
+
+    #[test]
+    fn test_valid_animation() {
+        let chunks = animation(&[(10, 10, 0, 0), (5, 5, 5, 5), (1, 10, 9, 0)]);
+        assert!(validate_chunks(&chunks).is_empty());
+    }
+
+    #[test]
+    fn test_validate_chunk_refs() {
+        let chunks = animation(&[(10, 10, 0, 0), (5, 5, 6, 0)]);
+        let bytes = Png::from_chunks(chunks.clone()).as_bytes();
+        let png_ref = PngRef::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(
+            validate_chunk_refs(png_ref.chunks()),
+            validate_chunks(&chunks)
+        );
+    }
+
+    #[test]
+    fn test_frame_out_of_bounds() {
+        let chunks = animation(&[(10, 10, 0, 0), (5, 5, 6, 0)]);
+        assert_eq!(
+            validate_chunks(&chunks),
+            vec![Violation::FrameOutOfBounds {
+                index: 4,
+                frame: (5, 5, 6, 0),
+                image: (10, 10)
+            }]
+        );
+    }
+
+    #[test]
+    fn test_frame_sequence_and_count() {
+        let mut chunks = animation(&[(10, 10, 0, 0), (5, 5, 0, 0), (5, 5, 0, 0)]);
+        // Drop the second frame's fdAT
+        chunks.remove(5);
+        assert_eq!(
+            validate_chunks(&chunks),
+            vec![Violation::SequenceOutOfOrder {
+                chunk_type: "fcTL".to_string(),
+                index: 5,
+                expected: 2,
+                found: 3
+            }]
+        );
+
+        chunks.remove(5);
+        let violations = validate_chunks(&chunks);
+        assert!(violations.contains(&Violation::FrameCountMismatch {
+            declared: 3,
+            found: 2
+        }));
+    }
+}
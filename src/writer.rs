@@ -0,0 +1,94 @@
+use std::io::Write;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::error::{PngError, Result};
+use crate::png::Png;
+
+/// Writes a PNG stream one `Chunk` at a time to any `Write` sink.
+///
+/// The signature is written when the writer is created. Each chunk is written
+/// directly from its data along with its cached CRC, so no serialized copy of the
+/// chunk is built. Writing stops being allowed once IEND has been written.
+#[derive(Debug)]
+pub struct PngWriter<W: Write> {
+    writer: W,
+    finished: bool,
+}
+
+impl<W: Write> PngWriter<W> {
+    /// Creates a `PngWriter`, writing the 8 byte PNG signature.
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(&Png::STANDARD_HEADER)?;
+
+        Ok(PngWriter {
+            writer,
+            finished: false,
+        })
+    }
+
+    /// Writes a single chunk. Returns `PngError::ChunkAfterIend` if IEND has already
+    /// been written.
+    pub fn write_chunk(&mut self, chunk: &Chunk) -> Result<()> {
+        if self.finished {
+            return Err(PngError::ChunkAfterIend(*chunk.chunk_type()));
+        }
+
+        self.writer.write_all(&chunk.length().to_be_bytes())?;
+        self.writer.write_all(&chunk.chunk_type().bytes())?;
+        self.writer.write_all(chunk.data())?;
+        self.writer.write_all(&chunk.crc().to_be_bytes())?;
+
+        if *chunk.chunk_type() == ChunkType::IEND {
+            self.finished = true;
+        }
+        Ok(())
+    }
+
+    /// Whether IEND has been written.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Flushes and unwraps this `PngWriter`, returning the underlying writer.
+    pub fn into_inner(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(
+            ChunkType::from_str(chunk_type).unwrap(),
+            data.as_bytes().to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_writes_same_bytes_as_png() {
+        let chunks = vec![chunk("FrSt", "first"), chunk("IEND", "")];
+        let mut writer = PngWriter::new(Vec::new()).unwrap();
+        for chunk in &chunks {
+            writer.write_chunk(chunk).unwrap();
+        }
+        let bytes = writer.into_inner().unwrap();
+
+        assert_eq!(bytes, Png::from_chunks(chunks).as_bytes());
+    }
+
+    #[test]
+    fn test_refuses_chunk_after_iend() {
+        let mut writer = PngWriter::new(Vec::new()).unwrap();
+        writer.write_chunk(&chunk("IEND", "")).unwrap();
+        assert!(writer.is_finished());
+
+        let result = writer.write_chunk(&chunk("TeSt", "late"));
+        assert!(matches!(result, Err(PngError::ChunkAfterIend(_))));
+    }
+}